@@ -5,13 +5,19 @@ use specs::prelude::*;
 pub fn render(ecs: &World, ctx: &mut Rltk) {
     let (min_x, min_y, max_x, max_y) = get_bounds(ecs, ctx);
 
-    render_map(ecs, min_y, max_y, min_x, max_x, ctx);
+    render_map(&ecs.fetch::<Map>(), min_y, max_y, min_x, max_x, ctx);
 
     render_entities(ecs, min_x, min_y, ctx);
 }
 
+/// Renders a map generation snapshot around the player spawn, ignoring entities
+pub fn render_snapshot(ecs: &World, snapshot: &Map, ctx: &mut Rltk) {
+    let (min_x, min_y, max_x, max_y) = get_bounds(ecs, ctx);
+    render_map(snapshot, min_y, max_y, min_x, max_x, ctx);
+}
+
 pub fn get_bounds(ecs: &World, ctx: &mut Rltk) -> (i32, i32, i32, i32) {
-    let player_pos = (*ecs.fetch::<Player>()).position;
+    let player_pos = ecs.fetch::<Player>().position;
     let (x_dim, y_dim) = ctx.get_char_size();
     let (center_x, center_y) = (x_dim / 2, y_dim / 2);
     let min_x = player_pos.x - center_x as i32;
@@ -21,8 +27,7 @@ pub fn get_bounds(ecs: &World, ctx: &mut Rltk) -> (i32, i32, i32, i32) {
     (min_x, min_y, max_x, max_y)
 }
 
-fn render_map(ecs: &World, min_y: i32, max_y: i32, min_x: i32, max_x: i32, ctx: &mut Rltk) {
-    let map = ecs.fetch::<Map>();
+fn render_map(map: &Map, min_y: i32, max_y: i32, min_x: i32, max_x: i32, ctx: &mut Rltk) {
     let (map_width, map_height) = map.dimensions();
     for (y, ty) in (min_y..max_y).enumerate() {
         for (x, tx) in (min_x..max_x).enumerate() {
@@ -43,7 +48,7 @@ fn render_entities(ecs: &World, min_x: i32, min_y: i32, ctx: &mut Rltk) {
     let positions = ecs.read_storage::<Position>();
     let renderables = ecs.read_storage::<Renderable>();
     let mut to_render = (&positions, &renderables).par_join().collect::<Vec<_>>();
-    to_render.sort_by(|&(_, r1), &(_, r2)| r1.render_order.cmp(&r2.render_order));
+    to_render.sort_by_key(|&(_, r1)| r1.render_order);
     for (pos, render) in to_render.into_iter() {
         let idx = map.coords_to_idx(pos.x, pos.y);
         if map.visible[idx] {
@@ -1,4 +1,4 @@
-use crate::{map::Map, random};
+use crate::{dungeon::MasterDungeon, map::Map, random};
 use rltk::{Point, RGB};
 use serde::{Deserialize, Serialize};
 #[allow(deprecated)]
@@ -8,14 +8,22 @@ use specs::{
     saveload::{ConvertSaveload, Marker},
 };
 use specs_derive::{Component, ConvertSaveload};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-#[derive(Component, Debug, Clone, Copy, ConvertSaveload)]
+#[derive(Component, Debug, PartialEq, Clone, Copy, ConvertSaveload)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
+/// Position of an entity parked on a layer the player is not on
+#[derive(Component, Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct OtherLayerPosition {
+    pub x: i32,
+    pub y: i32,
+    pub layer: i32,
+}
+
 impl Position {
     pub fn as_point(&self) -> Point {
         Point {
@@ -47,6 +55,63 @@ pub struct Viewshed {
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Enemy;
 
+/// Who an entity sides with, decides how mobs react to each other
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Player,
+    /// Security drones and hunters, hostile to everyone who isn't on the payroll
+    Corporate,
+    /// Skels picking the layers clean
+    Scavengers,
+    /// Snakes and other wildlife
+    Feral,
+}
+
+/// Mobs hunting together, `size` is how many the pack started with
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Pack {
+    pub id: u64,
+    pub size: i32,
+}
+
+/// What a mob is up to between turns
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AiState {
+    #[default]
+    Idle,
+    /// Strolling towards a random spot
+    Wander { x: i32, y: i32 },
+    /// Going after a hostile, last seen at `x`, `y`
+    Chase { x: i32, y: i32 },
+    /// Running from whatever it sees
+    Flee,
+    /// Checking where a hostile was last seen, gives up after `turns`
+    Search { x: i32, y: i32, turns: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    Attack,
+    Ignore,
+    Flee,
+}
+
+impl Faction {
+    /// How a member of this faction reacts to seeing a member of `other`, `armed` if it can shoot
+    pub fn reaction(self, other: Faction, armed: bool) -> Reaction {
+        use Faction::*;
+        match (self, other) {
+            (a, b) if a == b => Reaction::Ignore,
+            (Corporate, _) | (Player, _) => Reaction::Attack,
+            (Scavengers, Corporate) => Reaction::Flee,
+            (Scavengers, _) => Reaction::Attack,
+            (Feral, Corporate) | (Feral, Scavengers) => Reaction::Flee,
+            (Feral, _) if armed => Reaction::Flee,
+            (Feral, _) => Reaction::Attack,
+        }
+    }
+}
+
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct Name {
     pub name: String,
@@ -76,18 +141,97 @@ pub struct MeleeAttack {
     pub target: Entity,
 }
 
+#[derive(PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize, Clone, Copy)]
+pub enum DamageType {
+    #[default]
+    Kinetic,
+    Thermal,
+    Electric,
+    Data,
+}
+
+impl std::fmt::Display for DamageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DamageType::Kinetic => "kinetic",
+            DamageType::Thermal => "thermal",
+            DamageType::Electric => "electric",
+            DamageType::Data => "data",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct Damage {
+    pub amount: i32,
+    pub kind: DamageType,
+    /// Whoever dealt the hit, `None` for things like burning, hits resolve before a save
+    #[serde(skip)]
+    pub source: Option<Entity>,
+}
+
+/// Lets a mob, or whoever equips this item, shoot at things
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct RangedWeapon {
+    pub range: i32,
+    pub damage: i32,
+    #[serde(default)]
+    pub kind: DamageType,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct RangedAttack {
+    pub target: Entity,
+}
+
+/// Every hit an entity took this turn, resistances are applied by `DamageSystem`
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct TakeDamage {
-    pub amount: i32,
+    pub hits: Vec<Damage>,
 }
 
 impl TakeDamage {
-    pub fn new_damage(store: &mut WriteStorage<Self>, target: Entity, amount: i32) {
-        let dmg = TakeDamage { amount };
-        store.insert(target, dmg).expect("Unable to insert damage");
+    pub fn new_damage(
+        store: &mut WriteStorage<Self>,
+        target: Entity,
+        amount: i32,
+        kind: DamageType,
+        source: Option<Entity>,
+    ) {
+        let hit = Damage {
+            amount,
+            kind,
+            source,
+        };
+        if let Some(dmg) = store.get_mut(target) {
+            dmg.hits.push(hit);
+        } else {
+            store
+                .insert(target, TakeDamage { hits: vec![hit] })
+                .expect("Unable to insert damage");
+        }
     }
 }
 
+/// The entity whose hit brought this one down
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct KilledBy {
+    pub killer: Entity,
+}
+
+/// Percent of each damage type shrugged off, negative values are weaknesses
+#[derive(Component, Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Resistances {
+    pub percent: HashMap<DamageType, i32>,
+}
+
+/// Damage type of attacks made without a weapon
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct NaturalAttack {
+    pub kind: DamageType,
+}
+
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Item;
 
@@ -152,18 +296,35 @@ pub enum Effect {
     DamageRanged {
         range: i32,
         damage: i32,
+        #[serde(default)]
+        kind: DamageType,
     },
     DamageAOE {
         range: i32,
         damage: i32,
         radius: i32,
+        #[serde(default)]
+        kind: DamageType,
     },
     Recharge(i32),
+    /// Buys turns on the trace timer
+    ExtendTrace(i32),
+    /// Freezes the trace timer for a number of turns
+    SpoofTrace(i32),
 }
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub enum EquipBonus {
     Defense(i32),
-    Attack(i32),
+    Attack {
+        amount: i32,
+        #[serde(default)]
+        kind: DamageType,
+    },
+    Resist {
+        kind: DamageType,
+        percent: i32,
+    },
+    Speed(i32),
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
@@ -172,6 +333,8 @@ pub struct SerializeMe;
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct SerializationHelper {
     pub map: Map,
+    pub dungeon: MasterDungeon,
+    pub seed: u64,
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
@@ -179,16 +342,228 @@ pub struct LevelUp {
     pub amount: i32,
 }
 
+/// Progress towards the next level, level ups not yet spent are `pending`
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+    pub pending: i32,
+}
+
+/// Stat points granted by every level
+pub const LEVEL_UP_POINTS: i32 = 2;
+
+impl Experience {
+    /// Experience needed to get from `level` to the next one
+    pub fn xp_to_next(level: i32) -> i32 {
+        20 * level + 5 * level * level
+    }
+
+    /// Adds experience, returns how many levels were gained
+    pub fn gain(&mut self, xp: i32) -> i32 {
+        self.xp += xp;
+        let mut gained = 0;
+        while self.xp >= Self::xp_to_next(self.level) {
+            self.xp -= Self::xp_to_next(self.level);
+            self.level += 1;
+            gained += 1;
+        }
+        self.pending += gained;
+        gained
+    }
+}
+
+#[test]
+fn experience_follows_the_curve() {
+    let mut exp = Experience {
+        level: 1,
+        xp: 0,
+        pending: 0,
+    };
+    assert_eq!(exp.gain(24), 0);
+    assert_eq!(exp.gain(1), 1);
+    assert_eq!((exp.level, exp.xp), (2, 0));
+    // Enough for two levels at once
+    assert_eq!(exp.gain(60 + 105), 2);
+    assert_eq!((exp.level, exp.xp, exp.pending), (4, 0, 3));
+}
+
+/// Experience awarded for killing this entity
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct XpValue {
+    pub xp: i32,
+}
+
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Particle {
     pub lifetime: f32,
 }
 
+/// Turns left until the corporation finds the player, past zero the pursuit escalates
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct TraceTimer {
     pub timer: i32,
+    #[serde(default)]
+    pub alert: i32,
+    /// Hunters dispatched but not yet on the map
+    #[serde(default)]
+    pub pending_hunters: i32,
+    /// Turns the trace stays frozen on a decoy
+    #[serde(default)]
+    pub spoofed: i32,
+}
+
+/// Corporate muscle that always knows where the player is
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Hunter;
+
+/// Programs the player can run by spending compute
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Ability {
+    /// Fries the circuits of a target
+    Overload,
+    /// Cuts a target off from its sensors for a while
+    Blind,
+    /// Scrambles the motor control of a target
+    Scramble,
+    /// Reveals the layout of the network around the player
+    Ping,
+}
+
+impl Ability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ability::Overload => "Overload",
+            Ability::Blind => "Blind",
+            Ability::Scramble => "Scramble",
+            Ability::Ping => "Ping",
+        }
+    }
+
+    pub fn cost(&self) -> i32 {
+        match self {
+            Ability::Overload => 4,
+            Ability::Blind => 2,
+            Ability::Scramble => 3,
+            Ability::Ping => 3,
+        }
+    }
+
+    /// Turns taken off the trace timer every time the program runs
+    pub fn trace_cost(&self) -> i32 {
+        match self {
+            Ability::Overload => 8,
+            Ability::Blind => 3,
+            Ability::Scramble => 5,
+            Ability::Ping => 12,
+        }
+    }
+
+    /// Targeting range, `None` for programs that run on the player
+    pub fn range(&self) -> Option<i32> {
+        match self {
+            Ability::Overload => Some(6),
+            Ability::Blind => Some(6),
+            Ability::Scramble => Some(6),
+            Ability::Ping => None,
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct KnownAbilities {
+    pub abilities: Vec<Ability>,
 }
 
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToHack {
+    pub ability: Ability,
+    pub target: Target,
+}
+
+/// A lasting effect and how many of the victim's turns it lasts
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum StatusEffect {
+    Stun(i32),
+    Burn { turns: i32, damage: i32 },
+    Emp(i32),
+    Confuse(i32),
+    Blind(i32),
+}
+
+/// Status effects waiting to be applied by the status system
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct InflictStatus {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl InflictStatus {
+    pub fn new_status(store: &mut WriteStorage<Self>, target: Entity, effect: StatusEffect) {
+        if let Some(pending) = store.get_mut(target) {
+            pending.effects.push(effect);
+        } else {
+            store
+                .insert(
+                    target,
+                    InflictStatus {
+                        effects: vec![effect],
+                    },
+                )
+                .expect("Unable to insert status");
+        }
+    }
+}
+
+/// Items inflict their status on whatever they hit, mobs on a successful melee hit
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Inflicts {
+    pub status: StatusEffect,
+    /// Percent chance to inflict the status
+    pub chance: i32,
+}
+
+/// Loses every turn
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Stunned {
+    pub turns: i32,
+}
+
+/// Takes `damage` every turn
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Burning {
+    pub turns: i32,
+    pub damage: i32,
+}
+
+/// Can't spend compute
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Emp {
+    pub turns: i32,
+}
+
+/// Moves in random directions
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Confused {
+    pub turns: i32,
+}
+
+/// Sees nothing but its own tile
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Blinded {
+    pub turns: i32,
+}
+
+/// Energy grows by `speed` every tick, a turn costs `initiative::ACTION_COST`
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Initiative {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+/// Given to everyone allowed to act this tick
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MyTurn;
+
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Drops {
     pub table: random::Table,
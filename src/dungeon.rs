@@ -0,0 +1,131 @@
+use crate::{
+    components::{OtherLayerPosition, Particle, Position},
+    map::Map,
+    player::Player,
+};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashMap;
+
+/// Maps of every layer the player has left, entities stay in the world
+/// with an `OtherLayerPosition` until the player comes back
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MasterDungeon {
+    maps: HashMap<i32, Map>,
+}
+
+impl MasterDungeon {
+    pub fn store(&mut self, map: Map) {
+        self.maps.insert(map.layer, map);
+    }
+
+    pub fn take(&mut self, layer: i32) -> Option<Map> {
+        self.maps.remove(&layer)
+    }
+
+    pub fn has_layer(&self, layer: i32) -> bool {
+        self.maps.contains_key(&layer)
+    }
+}
+
+/// Stores the current map and parks everything on it but the player
+pub fn freeze_layer(ecs: &mut World) {
+    let map = Map::clone(&ecs.fetch::<Map>());
+    let layer = map.layer;
+    ecs.write_resource::<MasterDungeon>().store(map);
+
+    let entities = ecs.entities();
+    let player = ecs.fetch::<Player>();
+    let particles = ecs.read_storage::<Particle>();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_positions = ecs.write_storage::<OtherLayerPosition>();
+
+    let frozen = (&entities, &positions, !&particles)
+        .join()
+        .filter(|&(e, _, _)| e != player.entity)
+        .map(|(e, pos, _)| (e, *pos))
+        .collect::<Vec<_>>();
+    for (e, pos) in frozen {
+        positions.remove(e);
+        other_positions
+            .insert(
+                e,
+                OtherLayerPosition {
+                    x: pos.x,
+                    y: pos.y,
+                    layer,
+                },
+            )
+            .expect("Failed to freeze entity");
+    }
+}
+
+/// Makes a previously visited layer current again, returns `false` if it was never visited
+pub fn thaw_layer(ecs: &mut World, layer: i32) -> bool {
+    let Some(mut map) = ecs.write_resource::<MasterDungeon>().take(layer) else {
+        return false;
+    };
+    map.reset_runtime_state();
+    *ecs.write_resource::<Map>() = map;
+
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_positions = ecs.write_storage::<OtherLayerPosition>();
+
+    let thawed = (&entities, &other_positions)
+        .join()
+        .filter(|(_, pos)| pos.layer == layer)
+        .map(|(e, pos)| (e, *pos))
+        .collect::<Vec<_>>();
+    for (e, pos) in thawed {
+        other_positions.remove(e);
+        positions
+            .insert(e, Position { x: pos.x, y: pos.y })
+            .expect("Failed to thaw entity");
+    }
+    true
+}
+
+#[test]
+fn layer_round_trip() {
+    use crate::components::Name;
+
+    let mut ecs = World::new();
+    ecs.register::<Position>();
+    ecs.register::<OtherLayerPosition>();
+    ecs.register::<Particle>();
+    ecs.register::<Name>();
+    ecs.insert(MasterDungeon::default());
+
+    let player = ecs.create_entity().with(Position { x: 1, y: 1 }).build();
+    ecs.insert(Player {
+        entity: player,
+        position: Position { x: 1, y: 1 },
+    });
+    let mob = ecs
+        .create_entity()
+        .with(Position { x: 5, y: 7 })
+        .with(Name {
+            name: "Skel".into(),
+        })
+        .build();
+    let mut first = Map::new(16, 16, 0);
+    first.revealed[3] = true;
+    ecs.insert(first.clone());
+
+    freeze_layer(&mut ecs);
+    assert!(ecs.read_storage::<Position>().get(mob).is_none());
+    assert!(ecs.read_storage::<Position>().get(player).is_some());
+    *ecs.write_resource::<Map>() = Map::new(16, 16, 1);
+    assert!(!thaw_layer(&mut ecs, 2));
+
+    freeze_layer(&mut ecs);
+    assert!(thaw_layer(&mut ecs, 0));
+    assert_eq!(*ecs.fetch::<Map>(), first);
+    assert_eq!(
+        ecs.read_storage::<Position>().get(mob),
+        Some(&Position { x: 5, y: 7 })
+    );
+    assert!(ecs.fetch::<MasterDungeon>().has_layer(1));
+    assert!(!ecs.fetch::<MasterDungeon>().has_layer(0));
+}
@@ -1,7 +1,7 @@
 #![allow(deprecated)]
 use std::{fs::File, path::Path};
 
-use crate::{components::*, map::Map, player::Player};
+use crate::{components::*, dungeon::MasterDungeon, map::Map, player::Player, random::RunSeed};
 use specs::{
     error::NoError,
     prelude::*,
@@ -42,9 +42,15 @@ macro_rules! deserialize_individually {
 
 pub fn save_game(ecs: &mut World) {
     let mapcopy = ecs.get_mut::<Map>().unwrap().clone();
+    let dungeon = MasterDungeon::clone(&ecs.fetch::<MasterDungeon>());
+    let seed = ecs.fetch::<RunSeed>().0;
     let savehelper = ecs
         .create_entity()
-        .with(SerializationHelper { map: mapcopy })
+        .with(SerializationHelper {
+            map: mapcopy,
+            dungeon,
+            seed,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
@@ -61,14 +67,25 @@ pub fn save_game(ecs: &mut World) {
             serializer,
             data,
             Position,
+            OtherLayerPosition,
             Renderable,
             Control,
             Viewshed,
             Enemy,
+            Faction,
+            AiState,
+            Pack,
+            Hunter,
+            TraceTimer,
             Name,
             Blocker,
             Stats,
             TakeDamage,
+            KilledBy,
+            RangedWeapon,
+            RangedAttack,
+            Resistances,
+            NaturalAttack,
             MeleeAttack,
             Item,
             Consumable,
@@ -82,6 +99,19 @@ pub fn save_game(ecs: &mut World) {
             Effect,
             EquipBonus,
             LevelUp,
+            Experience,
+            XpValue,
+            Drops,
+            Initiative,
+            KnownAbilities,
+            WantsToHack,
+            InflictStatus,
+            Inflicts,
+            Stunned,
+            Burning,
+            Emp,
+            Confused,
+            Blinded,
             SerializationHelper
         );
     }
@@ -106,14 +136,25 @@ pub fn load_game(ecs: &mut World) {
             de,
             d,
             Position,
+            OtherLayerPosition,
             Renderable,
             Control,
             Viewshed,
             Enemy,
+            Faction,
+            AiState,
+            Pack,
+            Hunter,
+            TraceTimer,
             Name,
             Blocker,
             Stats,
             TakeDamage,
+            KilledBy,
+            RangedWeapon,
+            RangedAttack,
+            Resistances,
+            NaturalAttack,
             MeleeAttack,
             Item,
             Consumable,
@@ -127,6 +168,19 @@ pub fn load_game(ecs: &mut World) {
             Effect,
             EquipBonus,
             LevelUp,
+            Experience,
+            XpValue,
+            Drops,
+            Initiative,
+            KnownAbilities,
+            WantsToHack,
+            InflictStatus,
+            Inflicts,
+            Stunned,
+            Burning,
+            Emp,
+            Confused,
+            Blinded,
             SerializationHelper
         );
     }
@@ -140,10 +194,9 @@ pub fn load_game(ecs: &mut World) {
         for (e, h) in (&entities, &helper).join() {
             let mut worldmap = ecs.write_resource::<Map>();
             *worldmap = h.map.clone();
-            worldmap.tile_content = vec![Vec::new(); h.map.size()];
-            worldmap.visible = vec![false; h.map.size()];
-            worldmap.passable = vec![false; h.map.size()];
-            worldmap.populate_passable();
+            worldmap.reset_runtime_state();
+            *ecs.write_resource::<MasterDungeon>() = h.dungeon.clone();
+            ecs.write_resource::<RunSeed>().0 = h.seed;
             deleteme = Some(e);
         }
         let mut vis = ecs.write_storage::<Viewshed>();
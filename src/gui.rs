@@ -3,12 +3,14 @@ use std::cmp::min;
 use crate::{
     camera,
     components::{
-        Consumable, Control, Equippable, Equipped, InInventory, Name, Position, Slot, Slots, Stats,
+        Ability, Blinded, Burning, Confused, Consumable, Control, Emp, Equippable, Equipped,
+        Experience, InInventory, KnownAbilities, Name, Position, Slot, Slots, Stats, Stunned,
         TraceTimer, Viewshed,
     },
     map::Map,
     player::Player,
     state::RunState,
+    systems::trace_timer::{DOWNLOAD_COST, SCRUB_BONUS, SCRUB_COMPUTE},
     DBG_SHOW_COORDINATE_TOOLTIP,
 };
 use rltk::{Point, Rltk, VirtualKeyCode, RGB};
@@ -61,15 +63,12 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     let equipped = ecs.read_storage::<Equipped>();
     let slots = ecs.read_storage::<Slots>();
     let names = ecs.read_storage::<Name>();
+    let experience = ecs.read_storage::<Experience>();
 
     ctx.print_centered(43, format!("LAYER#{}", map.layer));
 
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
-        if y < 49 {
-            ctx.print(42, y, s);
-        }
-        y += 1;
+    for (y, s) in (44..49).zip(log.entries.iter().rev()) {
+        ctx.print(42, y, s);
     }
 
     let player = ecs.fetch::<Player>();
@@ -78,7 +77,28 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         .get(player.entity)
         .expect("Player has no trace component");
 
-    ctx.print(70, 43, format!("TRACE:{}", trace.timer));
+    // A spoofed trace is frozen
+    let trace_color = if trace.spoofed > 0 {
+        rltk::GREEN
+    } else {
+        rltk::WHITE
+    };
+    ctx.print_color(
+        70,
+        43,
+        RGB::named(trace_color),
+        RGB::named(rltk::BLACK),
+        format!("TRACE:{}", trace.timer),
+    );
+    if trace.alert > 0 {
+        ctx.print_color(
+            60,
+            43,
+            RGB::named(rltk::RED),
+            RGB::named(rltk::BLACK),
+            format!("ALERT:{}", trace.alert),
+        );
+    }
 
     for (_, stats) in (&player_control, &stats).join() {
         let player_items = (&equipped, &names)
@@ -111,7 +131,32 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         ctx.print(13, 44, format!("ATK:{}", stats.base_power));
         ctx.print(13, 45, format!("DEF:{}", stats.base_defense));
         ctx.print(13, 46, format!("CMP:{}", stats.base_compute));
-        ctx.print(13, 46, format!("HLT:{}", stats.base_hp));
+        ctx.print(13, 47, format!("HLT:{}", stats.base_hp));
+
+        if let Some(exp) = experience.get(player.entity) {
+            ctx.print(21, 47, format!("LVL:{}", exp.level));
+            ctx.draw_bar_horizontal(
+                27,
+                47,
+                14,
+                exp.xp,
+                Experience::xp_to_next(exp.level),
+                RGB::named(rltk::GOLD),
+                RGB::named(rltk::BLACK),
+            );
+        }
+
+        let statuses = status_names(ecs, player.entity)
+            .into_iter()
+            .map(|s| s[..3].to_uppercase())
+            .collect::<Vec<_>>();
+        ctx.print_color(
+            13,
+            48,
+            RGB::named(rltk::ORANGE),
+            RGB::named(rltk::BLACK),
+            statuses.join(" "),
+        );
 
         const MAX_BARS: i32 = 5;
         let health = format!("{}/{}", stats.hp, stats.base_hp);
@@ -149,6 +194,27 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     draw_tooltips(ecs, ctx)
 }
 
+/// Status effects currently on an entity, as shown in tooltips and the status bar
+pub fn status_names(ecs: &World, entity: Entity) -> Vec<&'static str> {
+    let mut statuses = Vec::new();
+    if ecs.read_storage::<Stunned>().contains(entity) {
+        statuses.push("stunned");
+    }
+    if ecs.read_storage::<Burning>().contains(entity) {
+        statuses.push("burning");
+    }
+    if ecs.read_storage::<Emp>().contains(entity) {
+        statuses.push("jammed");
+    }
+    if ecs.read_storage::<Confused>().contains(entity) {
+        statuses.push("confused");
+    }
+    if ecs.read_storage::<Blinded>().contains(entity) {
+        statuses.push("blind");
+    }
+    statuses
+}
+
 fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let (min_x, min_y, _, _) = camera::get_bounds(ecs, ctx);
     let map = ecs.fetch::<Map>();
@@ -162,14 +228,19 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     if mouse_pos.0 >= map.dim_x || mouse_pos.1 >= map.dim_y || mouse_pos.0 < 1 || mouse_pos.1 < 1 {
         return;
     }
-    let tooltip: Vec<String> = (&names, &positions)
-        .par_join()
-        .filter_map(|(name, position)| {
+    let tooltip: Vec<String> = (&ecs.entities(), &names, &positions)
+        .join()
+        .filter_map(|(entity, name, position)| {
             if position.x == map_mouse_pos.0
                 && position.y == map_mouse_pos.1
                 && map.is_visible(position.x, position.y)
             {
-                Some(name.name.clone())
+                let statuses = status_names(ecs, entity);
+                if statuses.is_empty() {
+                    Some(name.name.clone())
+                } else {
+                    Some(format!("{} ({})", name.name, statuses.join(", ")))
+                }
             } else {
                 None
             }
@@ -188,8 +259,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
         if mouse_pos.0 > 40 {
             let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             let left_x = mouse_pos.0 - width;
-            let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
+            for (y, s) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x,
                     y,
@@ -204,23 +274,21 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         y,
                         RGB::named(rltk::BLACK),
                         RGB::named(rltk::GREY),
-                        &" ".to_string(),
+                        " ".to_string(),
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x,
                 arrow_pos.y,
                 RGB::named(rltk::BLACK),
                 RGB::named(rltk::GREY),
-                &"->".to_string(),
+                "->".to_string(),
             );
         } else {
             let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
             let left_x = mouse_pos.0 + 3;
-            let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
+            for (y, s) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x + 1,
                     y,
@@ -235,17 +303,16 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         y,
                         RGB::named(rltk::BLACK),
                         RGB::named(rltk::GREY),
-                        &" ".to_string(),
+                        " ".to_string(),
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x,
                 arrow_pos.y,
                 RGB::named(rltk::BLACK),
                 RGB::named(rltk::GREY),
-                &"<-".to_string(),
+                "<-".to_string(),
             );
         }
     } else if DBG_SHOW_COORDINATE_TOOLTIP {
@@ -285,7 +352,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                 y,
                 RGB::named(rltk::BLACK),
                 RGB::named(rltk::GREY),
-                &" ".to_string(),
+                " ".to_string(),
             );
         }
     }
@@ -297,9 +364,16 @@ pub enum ItemMenuResult {
     Selected(Entity),
 }
 
-fn popup_menu<'a>(ctx: &mut Rltk, entries: impl Iterator<Item = &'a str> + Clone) {
+const CANCEL_HINT: &str = "ESCAPE to cancel";
+
+fn popup_menu<S: AsRef<str>>(
+    ctx: &mut Rltk,
+    title: &str,
+    footer: &str,
+    entries: impl Iterator<Item = S> + Clone,
+) {
     let count = entries.clone().count() as i32;
-    let mut y = 25 - (count / 2);
+    let y = 25 - (count / 2);
     ctx.draw_box(
         15,
         y - 2,
@@ -313,16 +387,16 @@ fn popup_menu<'a>(ctx: &mut Rltk, entries: impl Iterator<Item = &'a str> + Clone
         y - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Inventory",
+        title,
     );
     ctx.print_color(
         18,
         y + count + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        footer,
     );
-    for (j, name) in entries.enumerate() {
+    for (j, (y, name)) in (y..).zip(entries).enumerate() {
         ctx.set(
             17,
             y,
@@ -345,8 +419,7 @@ fn popup_menu<'a>(ctx: &mut Rltk, entries: impl Iterator<Item = &'a str> + Clone
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, name);
-        y += 1;
+        ctx.print(21, y, name.as_ref());
     }
 }
 
@@ -369,7 +442,12 @@ pub fn show_inventory(ecs: &mut World, ctx: &mut Rltk) -> ItemMenuResult {
         .collect::<Vec<_>>();
     let count = inventory.len() as i32;
 
-    popup_menu(ctx, inventory.iter().map(|&(_, n)| -> &str { n }));
+    popup_menu(
+        ctx,
+        "Inventory",
+        CANCEL_HINT,
+        inventory.iter().map(|&(_, n)| n),
+    );
 
     match ctx.key {
         None => ItemMenuResult::NoResponse,
@@ -398,16 +476,22 @@ pub enum LevelUpMenuResult {
     Selected(&'static str),
 }
 
-pub fn show_levelup(ctx: &mut Rltk) -> LevelUpMenuResult {
+/// Level ups earned in a fight can't be put off, only the ones from items
+pub fn show_levelup(ctx: &mut Rltk, cancellable: bool) -> LevelUpMenuResult {
     const STATS: [&str; 4] = ["DEF", "ATK", "CMP", "HLT"];
     let count = STATS.len() as i32;
 
-    popup_menu(ctx, STATS.into_iter());
+    let footer = if cancellable {
+        CANCEL_HINT
+    } else {
+        "Pick a stat to raise"
+    };
+    popup_menu(ctx, "Level up", footer, STATS.into_iter());
 
     match ctx.key {
         None => LevelUpMenuResult::NoResponse,
         Some(key) => match key {
-            VirtualKeyCode::Escape => LevelUpMenuResult::Cancel,
+            VirtualKeyCode::Escape if cancellable => LevelUpMenuResult::Cancel,
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if selection > -1 && selection < count {
@@ -421,10 +505,107 @@ pub fn show_levelup(ctx: &mut Rltk) -> LevelUpMenuResult {
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum TerminalAction {
+    DownloadMap,
+    ScrubLogs,
+}
+
+pub enum TerminalMenuResult {
+    Cancel,
+    NoResponse,
+    Selected(TerminalAction),
+}
+
+pub fn show_terminal(ctx: &mut Rltk) -> TerminalMenuResult {
+    let actions = [
+        (
+            TerminalAction::DownloadMap,
+            format!("Download layer map (trace -{})", DOWNLOAD_COST),
+        ),
+        (
+            TerminalAction::ScrubLogs,
+            format!(
+                "Scrub access logs ({} CMP, trace +{})",
+                SCRUB_COMPUTE, SCRUB_BONUS
+            ),
+        ),
+    ];
+    let count = actions.len() as i32;
+
+    popup_menu(
+        ctx,
+        "Service terminal",
+        CANCEL_HINT,
+        actions.iter().map(|(_, s)| s),
+    );
+
+    match ctx.key {
+        None => TerminalMenuResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Escape => TerminalMenuResult::Cancel,
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count {
+                    TerminalMenuResult::Selected(actions[selection as usize].0)
+                } else {
+                    TerminalMenuResult::NoResponse
+                }
+            }
+        },
+    }
+}
+
+pub enum AbilityMenuResult {
+    Cancel,
+    NoResponse,
+    Selected(Ability),
+}
+
+pub fn show_abilities(ecs: &mut World, ctx: &mut Rltk) -> AbilityMenuResult {
+    let player = ecs.fetch::<Player>();
+    let known = ecs.read_storage::<KnownAbilities>();
+    let stats = ecs.read_storage::<Stats>();
+    let abilities = known
+        .get(player.entity)
+        .map(|k| k.abilities.clone())
+        .unwrap_or_default();
+    let compute = stats.get(player.entity).map_or(0, |s| s.compute);
+    let count = abilities.len() as i32;
+
+    popup_menu(
+        ctx,
+        "Programs",
+        CANCEL_HINT,
+        abilities
+            .iter()
+            .map(|a| format!("{} ({} CMP)", a.name(), a.cost())),
+    );
+
+    match ctx.key {
+        None => AbilityMenuResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Escape => AbilityMenuResult::Cancel,
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count {
+                    let ability = abilities[selection as usize];
+                    if ability.cost() <= compute {
+                        AbilityMenuResult::Selected(ability)
+                    } else {
+                        AbilityMenuResult::NoResponse
+                    }
+                } else {
+                    AbilityMenuResult::NoResponse
+                }
+            }
+        },
+    }
+}
+
 pub enum TargetingResult {
     Cancel,
     Tile(i32, i32),
-    Entity(Entity),
     NoResponse,
 }
 
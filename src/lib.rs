@@ -1,12 +1,15 @@
 pub mod camera;
 pub mod components;
+pub mod dungeon;
 pub mod game_save;
 pub mod gui;
 pub mod map;
 pub mod player;
 pub mod random;
+pub mod raws;
 pub mod spawner;
 pub mod state;
 pub mod systems;
 
 const DBG_SHOW_COORDINATE_TOOLTIP: bool = false;
+const DBG_SHOW_MAPGEN: bool = false;
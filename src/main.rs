@@ -1,9 +1,12 @@
 use cassiopea::{
     components::*,
+    dungeon::MasterDungeon,
     gui::{GameLog, MainMenuSelection},
+    random::RunSeed,
+    raws::{RawMaster, RAWS_PATH},
     spawner::player,
     state::{RunState, State},
-    systems::particle,
+    systems::{navigation::Navigation, noise::NoiseQueue, particle},
 };
 use rltk::RGBA;
 use specs::prelude::*;
@@ -23,15 +26,25 @@ fn main() -> rltk::BError {
     gs.ecs.register::<SerializationHelper>();
 
     gs.ecs.register::<Position>();
+    gs.ecs.register::<OtherLayerPosition>();
     gs.ecs.register::<Renderable>();
     gs.ecs.register::<Control>();
     gs.ecs.register::<Viewshed>();
     gs.ecs.register::<Enemy>();
+    gs.ecs.register::<Faction>();
+    gs.ecs.register::<AiState>();
+    gs.ecs.register::<Pack>();
+    gs.ecs.register::<Hunter>();
     gs.ecs.register::<Name>();
     gs.ecs.register::<Blocker>();
     gs.ecs.register::<Stats>();
     gs.ecs.register::<MeleeAttack>();
     gs.ecs.register::<TakeDamage>();
+    gs.ecs.register::<KilledBy>();
+    gs.ecs.register::<RangedWeapon>();
+    gs.ecs.register::<RangedAttack>();
+    gs.ecs.register::<Resistances>();
+    gs.ecs.register::<NaturalAttack>();
     gs.ecs.register::<Item>();
     gs.ecs.register::<Consumable>();
     gs.ecs.register::<Equippable>();
@@ -44,13 +57,32 @@ fn main() -> rltk::BError {
     gs.ecs.register::<WantsToPickUp>();
     gs.ecs.register::<WantsToUseItem>();
     gs.ecs.register::<LevelUp>();
+    gs.ecs.register::<Experience>();
+    gs.ecs.register::<XpValue>();
     gs.ecs.register::<Particle>();
     gs.ecs.register::<TraceTimer>();
     gs.ecs.register::<Drops>();
+    gs.ecs.register::<Initiative>();
+    gs.ecs.register::<MyTurn>();
+    gs.ecs.register::<KnownAbilities>();
+    gs.ecs.register::<WantsToHack>();
+    gs.ecs.register::<InflictStatus>();
+    gs.ecs.register::<Inflicts>();
+    gs.ecs.register::<Stunned>();
+    gs.ecs.register::<Burning>();
+    gs.ecs.register::<Emp>();
+    gs.ecs.register::<Confused>();
+    gs.ecs.register::<Blinded>();
+
+    let raws = RawMaster::load(RAWS_PATH).unwrap_or_else(|e| panic!("{}", e));
+    gs.ecs.insert(raws);
 
     let player = player(&mut gs.ecs);
     gs.ecs.insert(player);
     let seed = rltk::RandomNumberGenerator::new().rand::<u64>();
+    gs.ecs.insert(RunSeed(seed));
+    gs.ecs.insert(MasterDungeon::default());
+    gs.ecs.insert(Navigation::default());
     gs.ecs.insert(rltk::RandomNumberGenerator::seeded(seed));
 
     gs.generate_map(128, 128, 0);
@@ -59,6 +91,7 @@ fn main() -> rltk::BError {
         .insert(RunState::MainMenu(MainMenuSelection::NewGame));
     gs.ecs.insert(GameLog::default());
     gs.ecs.insert(particle::RequestQueue::new());
+    gs.ecs.insert(NoiseQueue::new());
 
     rltk::main_loop(context, gs)
 }
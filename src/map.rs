@@ -15,9 +15,11 @@ pub enum Tile {
     TerminalDown,
     TerminalUp,
     TerminalService,
+    /// Never opened or shut, anything walks through but it blocks sight, shots and noise
+    Door,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
 pub struct Rect {
     top_left: (i32, i32),
     bottom_right: (i32, i32),
@@ -73,7 +75,7 @@ fn intersecting() {
     assert! {!a.intersects(&b)};
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Map {
     inner: Vec<Tile>,
     pub rooms: Vec<Rect>,
@@ -128,15 +130,22 @@ impl Map {
         }
     }
 
+    /// Rebuilds the fields that are not saved with the map
+    pub fn reset_runtime_state(&mut self) {
+        self.tile_content = vec![Vec::new(); self.size()];
+        self.visible = vec![false; self.size()];
+        self.passable = vec![false; self.size()];
+        self.populate_passable();
+    }
+
+    pub fn find_tile(&self, tile: Tile) -> Option<(i32, i32)> {
+        let idx = self.inner.iter().position(|&t| t == tile)?;
+        Some(self.idx_to_coords(idx))
+    }
+
     pub fn populate_passable(&mut self) {
-        for (i, tile) in self.inner.iter().enumerate() {
-            const PASSABLE_TILES: [Tile; 4] = [
-                Tile::Floor,
-                Tile::TerminalDown,
-                Tile::TerminalService,
-                Tile::TerminalUp,
-            ];
-            self.passable[i] = PASSABLE_TILES.contains(tile);
+        for i in 0..self.inner.len() {
+            self.passable[i] = self.is_walkable(i);
         }
     }
 
@@ -168,14 +177,65 @@ impl Map {
         }
     }
 
-    fn exit_valid(&self, x: i32, y: i32) -> bool {
+    fn exit_valid(&self, x: i32, y: i32, open: &impl Fn(usize) -> bool) -> bool {
         if x < 1 || x > self.dim_x - 1 || y < 1 || y > self.dim_y - 1 {
             false
         } else {
-            self.passable[self.coords_to_idx(x, y)]
+            open(self.coords_to_idx(x, y))
         }
     }
 
+    /// Whether the terrain of a tile can be walked on, whatever stands on it
+    pub fn is_walkable(&self, idx: usize) -> bool {
+        const PASSABLE_TILES: [Tile; 5] = [
+            Tile::Floor,
+            Tile::TerminalDown,
+            Tile::TerminalService,
+            Tile::TerminalUp,
+            Tile::Door,
+        ];
+        PASSABLE_TILES.contains(&self.inner[idx])
+    }
+
+    /// Neighbours of a tile that `open` lets through, with the cost of stepping there
+    pub fn exits_where(
+        &self,
+        idx: usize,
+        open: impl Fn(usize) -> bool,
+    ) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let mut exits = rltk::SmallVec::new();
+        let (x, y) = self.idx_to_coords(idx);
+        let w = self.dim_x as usize;
+
+        if self.exit_valid(x - 1, y, &open) {
+            exits.push((idx - 1, 1.0))
+        };
+        if self.exit_valid(x + 1, y, &open) {
+            exits.push((idx + 1, 1.0))
+        };
+        if self.exit_valid(x, y - 1, &open) {
+            exits.push((idx - w, 1.0))
+        };
+        if self.exit_valid(x, y + 1, &open) {
+            exits.push((idx + w, 1.0))
+        };
+
+        if self.exit_valid(x - 1, y - 1, &open) {
+            exits.push(((idx - w) - 1, 1.45));
+        }
+        if self.exit_valid(x + 1, y - 1, &open) {
+            exits.push(((idx - w) + 1, 1.45));
+        }
+        if self.exit_valid(x - 1, y + 1, &open) {
+            exits.push(((idx + w) - 1, 1.45));
+        }
+        if self.exit_valid(x + 1, y + 1, &open) {
+            exits.push(((idx + w) + 1, 1.45));
+        }
+
+        exits
+    }
+
     pub fn is_visible(&self, x: i32, y: i32) -> bool {
         let idx = self.coords_to_idx(x, y);
         self.visible[idx]
@@ -229,6 +289,15 @@ impl Map {
                 RGB::named(rltk::SKY_BLUE),
                 rltk::to_cp437('◙'),
             ),
+            Tile::Door => (
+                if self.visible[idx] {
+                    RGB::from_f32(0.6, 0.4, 0.1)
+                } else {
+                    RGB::from_f32(0.3, 0.2, 0.05)
+                },
+                RGB::named(rltk::BLACK),
+                rltk::to_cp437('+'),
+            ),
         }
     }
     fn is_revealed_wall(&self, x: i32, y: i32) -> bool {
@@ -299,41 +368,11 @@ impl AsRef<[Tile]> for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.inner[idx as usize] == Tile::Wall
+        matches!(self.inner[idx], Tile::Wall | Tile::Door)
     }
 
     fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
-        let (x, y) = self.idx_to_coords(idx);
-        let w = self.dim_x as usize;
-
-        if self.exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
-        };
-        if self.exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
-        };
-        if self.exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
-        };
-        if self.exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
-        };
-
-        if self.exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
-        }
-        if self.exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
-        }
-        if self.exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
-        }
-        if self.exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
-        }
-
-        exits
+        self.exits_where(idx, |i| self.passable[i])
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
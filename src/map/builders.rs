@@ -1,67 +1,273 @@
 use crate::{
-    components::Position,
-    map::{Map, Rect},
-    spawner,
+    components::{Pack, Position},
+    map::{Map, Rect, Tile},
+    random,
+    raws::{self, RawMaster},
+    DBG_SHOW_MAPGEN,
 };
 use rltk::RandomNumberGenerator;
 use specs::prelude::*;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 pub mod bsp;
+pub mod cave;
+pub mod cull;
+pub mod doors;
+pub mod drunkard;
+pub mod rounding;
 pub mod simple;
+pub mod terminals;
+pub mod widening;
 
-pub trait MapBuilder {
-    fn build(&mut self, rng: &mut RandomNumberGenerator);
-    fn get_map(&self) -> Map;
-    fn get_player_spawn(&self, rng: &mut RandomNumberGenerator) -> Position;
-    fn spawn(&mut self, ecs: &mut World);
-    fn take_snapshot(&mut self);
-    fn get_snapshots(&self) -> Vec<Map>;
+/// Everything a builder chain step works on
+pub struct BuilderMap {
+    pub map: Map,
+    pub starting_position: Option<Position>,
+    pub spawn_list: Vec<(usize, String)>,
+    /// Id and member tiles of every pack in `spawn_list`
+    pub packs: Vec<(u64, Vec<usize>)>,
+    pub history: Vec<Map>,
 }
 
-fn spawn_room(map: &Map, room: &Rect, ecs: &mut World) {
-    let mut spawn_points = HashSet::new();
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_spawns = rng.roll_dice(1, 4 + map.layer) - 1;
-        for _ in 0..num_spawns {
-            let mut added = false;
-            let mut tries = 0;
-            while !added && tries < 20 {
-                let (x1, y1, x2, y2) = room.coords();
-                let x = x1 + rng.roll_dice(1, i32::abs(x2 - x1));
-                let y = y1 + rng.roll_dice(1, i32::abs(y2 - y1));
-
-                added = spawn_points.insert((x, y));
-                if !added {
-                    tries += 1
-                }
+impl BuilderMap {
+    pub fn take_snapshot(&mut self) {
+        self.history.push(self.map.clone())
+    }
+
+    /// Records a step from inside a builder, only kept when the visualizer is on
+    pub fn take_progress_snapshot(&mut self) {
+        if DBG_SHOW_MAPGEN {
+            self.take_snapshot()
+        }
+    }
+}
+
+/// Produces the base layout of a map
+pub trait InitialMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+/// Post-processes a map produced by an initial builder
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap);
+}
+
+pub struct BuilderChain {
+    starter: Option<Box<dyn InitialMapBuilder>>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+    build_data: BuilderMap,
+}
+
+impl BuilderChain {
+    pub fn new(dim_x: i32, dim_y: i32, layer: i32) -> Self {
+        Self {
+            starter: None,
+            builders: Vec::new(),
+            build_data: BuilderMap {
+                map: Map::new(dim_x, dim_y, layer),
+                starting_position: None,
+                spawn_list: Vec::new(),
+                packs: Vec::new(),
+                history: Vec::new(),
+            },
+        }
+    }
+
+    pub fn start_with(mut self, starter: Box<dyn InitialMapBuilder>) -> Self {
+        if self.starter.is_some() {
+            panic!("Builder chain can only have one initial builder");
+        }
+        self.starter = Some(starter);
+        self
+    }
+
+    pub fn with(mut self, builder: Box<dyn MetaMapBuilder>) -> Self {
+        self.builders.push(builder);
+        self
+    }
+
+    pub fn build(&mut self, rng: &mut RandomNumberGenerator, raws: &RawMaster) {
+        let starter = self
+            .starter
+            .as_mut()
+            .expect("Builder chain has no initial builder");
+        starter.build_map(rng, &mut self.build_data);
+        self.build_data.take_snapshot();
+
+        for builder in self.builders.iter_mut() {
+            builder.build_map(rng, &mut self.build_data);
+            self.build_data.take_snapshot();
+        }
+        self.build_data.map.populate_passable();
+        self.populate(rng, &raws.spawn_table(self.build_data.map.layer));
+        self.gather_packs(rng, raws);
+    }
+
+    fn populate(&mut self, rng: &mut RandomNumberGenerator, spawn_table: &random::Table) {
+        let map = &self.build_data.map;
+        let spawn_list = &mut self.build_data.spawn_list;
+        if map.rooms.is_empty() {
+            for region in noise_regions(map, rng) {
+                spawn_region(map, &region, spawn_table, rng, spawn_list)
+            }
+        } else {
+            for room in &map.rooms {
+                spawn_room(map, room, spawn_table, rng, spawn_list)
+            }
+        }
+    }
+
+    /// Fills out a pack on free floor around every spawn that comes in packs
+    fn gather_packs(&mut self, rng: &mut RandomNumberGenerator, raws: &RawMaster) {
+        let build_data = &mut self.build_data;
+        let map = &build_data.map;
+        let mut taken = build_data
+            .spawn_list
+            .iter()
+            .map(|(idx, _)| *idx)
+            .chain(
+                build_data
+                    .starting_position
+                    .map(|pos| map.coords_to_idx(pos.x, pos.y)),
+            )
+            .collect::<HashSet<_>>();
+        for i in 0..build_data.spawn_list.len() {
+            let (leader, name) = build_data.spawn_list[i].clone();
+            let Some(pack) = raws.get(&name).and_then(|raw| raw.pack.as_ref()) else {
+                continue;
+            };
+            let size = rng.range(pack.min, pack.max + 1);
+            let id = rng.next_u64();
+            let (x, y) = map.idx_to_coords(leader);
+            let spots = (y - 2..=y + 2)
+                .flat_map(|sy| (x - 2..=x + 2).map(move |sx| (sx, sy)))
+                .filter(|&(sx, sy)| sx > 0 && sy > 0 && sx < map.dim_x - 1 && sy < map.dim_y - 1)
+                .map(|(sx, sy)| map.coords_to_idx(sx, sy))
+                .filter(|idx| map.inner[*idx] == Tile::Floor && !taken.contains(idx))
+                .take(size as usize - 1)
+                .collect::<Vec<_>>();
+
+            let mut members = vec![leader];
+            for idx in spots {
+                taken.insert(idx);
+                build_data.spawn_list.push((idx, name.clone()));
+                members.push(idx);
             }
+            build_data.packs.push((id, members));
         }
     }
 
-    let spawn_table = spawner::room_table(map.layer);
-    let spawns = {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        spawn_points
-            .into_iter()
-            .map(|s| (s, spawn_table.roll(&mut rng)))
-            .collect::<Vec<_>>()
-    };
-    for ((x, y), spawn) in spawns.into_iter() {
-        if let Some(spawn) = spawn {
-            match spawn {
-                "Skel" => spawner::skel(ecs, x, y),
-                "Snake" => spawner::snake(ecs, x, y),
-                "Healing cell" => spawner::healing_cell(ecs, x, y),
-                "Laser cell" => spawner::laser_cell(ecs, x, y),
-                "Compact missile" => spawner::compact_missile(ecs, x, y),
-                "Energy Shield" => spawner::energy_shield(ecs, x, y),
-                "Vibro Blade" => spawner::vibro_blade(ecs, x, y),
-                "Memory Shard" => spawner::memory_shard(ecs, x, y),
-                "Energy Cell" => spawner::energy_cell(ecs, x, y),
-                _ => {}
+    pub fn get_map(&self) -> Map {
+        self.build_data.map.clone()
+    }
+
+    pub fn get_player_spawn(&self) -> Position {
+        self.build_data
+            .starting_position
+            .expect("Builder chain did not place the player")
+    }
+
+    pub fn get_snapshots(&self) -> &[Map] {
+        &self.build_data.history
+    }
+
+    pub fn get_spawn_list(&self) -> &[(usize, String)] {
+        &self.build_data.spawn_list
+    }
+
+    pub fn get_packs(&self) -> &[(u64, Vec<usize>)] {
+        &self.build_data.packs
+    }
+
+    pub fn spawn(&self, ecs: &mut World) {
+        let map = &self.build_data.map;
+        let mut spawned = HashMap::new();
+        for (idx, name) in self.build_data.spawn_list.iter() {
+            let (x, y) = map.idx_to_coords(*idx);
+            if let Some(entity) = raws::spawn_named(ecs, name, x, y) {
+                spawned.insert(*idx, entity);
             }
         }
+        let mut packs = ecs.write_storage::<Pack>();
+        for (id, members) in self.build_data.packs.iter() {
+            let members = members
+                .iter()
+                .filter_map(|idx| spawned.get(idx))
+                .collect::<Vec<_>>();
+            let size = members.len() as i32;
+            for &&member in members.iter() {
+                packs
+                    .insert(member, Pack { id: *id, size })
+                    .expect("Unable to insert pack");
+            }
+        }
+    }
+}
+
+fn spawn_room(
+    map: &Map,
+    room: &Rect,
+    spawn_table: &random::Table,
+    rng: &mut RandomNumberGenerator,
+    spawn_list: &mut Vec<(usize, String)>,
+) {
+    let (x1, y1, x2, y2) = room.coords();
+    let area = (y1 + 1..=y2)
+        .flat_map(|y| (x1 + 1..=x2).map(move |x| (x, y)))
+        .map(|(x, y)| map.coords_to_idx(x, y))
+        .collect::<Vec<_>>();
+    spawn_region(map, &area, spawn_table, rng, spawn_list)
+}
+
+/// Picks mobs and items for an arbitrary set of tiles, with the same density as a room
+pub fn spawn_region(
+    map: &Map,
+    area: &[usize],
+    spawn_table: &random::Table,
+    rng: &mut RandomNumberGenerator,
+    spawn_list: &mut Vec<(usize, String)>,
+) {
+    let mut spawn_points = Vec::new();
+    let num_spawns = rng.roll_dice(1, 4 + map.layer) - 1;
+    for _ in 0..num_spawns {
+        let mut added = false;
+        let mut tries = 0;
+        while !added && tries < 20 {
+            let idx = match rng.random_slice_entry(area) {
+                Some(&idx) => idx,
+                None => return,
+            };
+
+            added = map.inner[idx] == Tile::Floor && !spawn_points.contains(&idx);
+            if added {
+                spawn_points.push(idx);
+            } else {
+                tries += 1
+            }
+        }
+    }
+
+    for idx in spawn_points {
+        if let Some(spawn) = spawn_table.roll(rng) {
+            spawn_list.push((idx, spawn.to_string()));
+        }
+    }
+}
+
+/// Splits the floor of a map into Voronoi-style regions using cellular noise
+pub fn noise_regions(map: &Map, rng: &mut RandomNumberGenerator) -> Vec<Vec<usize>> {
+    let mut noise = rltk::FastNoise::seeded(rng.next_u64());
+    noise.set_noise_type(rltk::NoiseType::Cellular);
+    noise.set_frequency(0.08);
+    noise.set_cellular_distance_function(rltk::CellularDistanceFunction::Manhattan);
+
+    let mut regions = BTreeMap::<i32, Vec<usize>>::new();
+    for (idx, tile) in map.inner.iter().enumerate() {
+        if *tile == Tile::Floor {
+            let (x, y) = map.idx_to_coords(idx);
+            let cell = (noise.get_noise(x as f32, y as f32) * 10240.) as i32;
+            regions.entry(cell).or_default().push(idx);
+        }
     }
+    regions.into_values().collect()
 }
@@ -3,30 +3,23 @@ use crate::{
     map::{Map, Rect, Tile},
 };
 use rltk::RandomNumberGenerator;
-use specs::prelude::*;
 
-use super::{spawn_room, MapBuilder};
+use super::{BuilderMap, InitialMapBuilder};
 
 pub struct BspBuilder {
-    map: Map,
-    // snapshots: Vec<Map>,
-    starting_position: Position,
     rects: Vec<Rect>,
 }
 
 impl BspBuilder {
-    pub fn new(dim_x: i32, dim_y: i32, layer: i32) -> Self {
-        Self {
-            map: Map::new(dim_x, dim_y, layer),
-            // snapshots: Vec::new(),
-            starting_position: Position { x: 0, y: 0 },
-            rects: Vec::new(),
-        }
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self { rects: Vec::new() }
     }
 
-    fn build(&mut self, rng: &mut RandomNumberGenerator) {
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
         self.rects
-            .push(Rect::new(1, 1, self.map.dim_x - 2, self.map.dim_y - 2));
+            .push(Rect::new(1, 1, map.dim_x - 2, map.dim_y - 2));
         let first = self.rects[0];
         self.subdivide(first);
 
@@ -35,45 +28,38 @@ impl BspBuilder {
             let rect = self.get_rekt(rng);
             let candidate = self.get_sub_rekt(rect, rng);
 
-            if self.is_possible(candidate) {
-                self.map.add_room(&candidate);
-                self.map.rooms.push(candidate);
+            if self.is_possible(&build_data.map, candidate) {
+                build_data.map.add_room(&candidate);
+                build_data.map.rooms.push(candidate);
+                build_data.take_progress_snapshot();
                 self.subdivide(rect);
             }
 
             n_rooms += 1;
         }
-        self.map
-            .rooms
-            .sort_by(|a, b| a.top_left.0.cmp(&b.top_left.0));
 
-        for i in 0..self.map.rooms.len() - 1 {
-            let room = self.map.rooms[i];
-            let next_room = self.map.rooms[i + 1];
+        build_data.map.rooms.sort_by_key(|a| a.top_left.0);
+
+        for i in 0..build_data.map.rooms.len() - 1 {
+            let room = build_data.map.rooms[i];
+            let next_room = build_data.map.rooms[i + 1];
             let start_x = room.x1() + (rng.roll_dice(1, i32::abs(room.x1() - room.x2())) - 1);
             let start_y = room.y1() + (rng.roll_dice(1, i32::abs(room.y1() - room.y2())) - 1);
             let end_x =
                 next_room.x1() + (rng.roll_dice(1, i32::abs(next_room.x1() - next_room.x2())) - 1);
             let end_y =
                 next_room.y1() + (rng.roll_dice(1, i32::abs(next_room.y1() - next_room.y2())) - 1);
-            self.draw_corridor(start_x, start_y, end_x, end_y);
+            Self::draw_corridor(&mut build_data.map, start_x, start_y, end_x, end_y);
+            build_data.take_progress_snapshot();
         }
 
-        let start = self.map.rooms[0].center();
-        self.starting_position = Position {
+        let start = build_data.map.rooms[0].center();
+        build_data.starting_position = Some(Position {
             x: start.0,
             y: start.1,
-        };
-        let coords = rng
-            .random_slice_entry(&self.map.rooms[1..])
-            .unwrap()
-            .center();
-        self.map[coords] = Tile::TerminalService;
-
-        let coords = self.map.rooms.last().unwrap().center();
-        self.map[coords] = Tile::TerminalDown;
+        });
     }
-    fn draw_corridor(&mut self, x1: i32, y1: i32, x2: i32, y2: i32) {
+    fn draw_corridor(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) {
         let mut x = x1;
         let mut y = y1;
 
@@ -87,10 +73,10 @@ impl BspBuilder {
             } else if y > y2 {
                 y -= 1;
             }
-            self.map[(x, y)] = Tile::Floor;
+            map[(x, y)] = Tile::Floor;
         }
     }
-    fn is_possible(&self, mut rect: Rect) -> bool {
+    fn is_possible(&self, map: &Map, mut rect: Rect) -> bool {
         rect.top_left.0 -= 2;
         rect.bottom_right.0 += 2;
         rect.top_left.1 -= 2;
@@ -100,10 +86,10 @@ impl BspBuilder {
 
         for y in rect.top_left.1..=rect.bottom_right.1 {
             for x in rect.top_left.0..=rect.bottom_right.0 {
-                if x > self.map.dim_x - 2 {
+                if x > map.dim_x - 2 {
                     can_build = false;
                 }
-                if y > self.map.dim_y - 2 {
+                if y > map.dim_y - 2 {
                     can_build = false;
                 }
                 if x < 1 {
@@ -112,7 +98,7 @@ impl BspBuilder {
                 if y < 1 {
                     can_build = false;
                 }
-                if can_build && self.map[(x, y)] != Tile::Wall {
+                if can_build && map[(x, y)] != Tile::Wall {
                     can_build = false;
                 }
             }
@@ -169,30 +155,8 @@ impl BspBuilder {
     }
 }
 
-impl MapBuilder for BspBuilder {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) {
-        self.build(rng)
-    }
-
-    fn get_map(&self) -> Map {
-        self.map.clone()
-    }
-
-    fn get_player_spawn(&self, _rng: &mut RandomNumberGenerator) -> Position {
-        self.starting_position
-    }
-
-    fn spawn(&mut self, ecs: &mut World) {
-        for room in &self.map.rooms {
-            spawn_room(&self.map, room, ecs)
-        }
-    }
-
-    fn take_snapshot(&mut self) {
-        todo!()
-    }
-
-    fn get_snapshots(&self) -> Vec<Map> {
-        todo!()
+impl InitialMapBuilder for BspBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data)
     }
 }
@@ -0,0 +1,90 @@
+use super::{BuilderMap, InitialMapBuilder};
+use crate::{
+    components::Position,
+    map::{Map, Tile},
+};
+use rltk::RandomNumberGenerator;
+
+const SMOOTHING_ITERATIONS: usize = 15;
+
+/// Garbage valleys: organic caves grown with cellular automata
+pub struct CaveBuilder;
+
+impl CaveBuilder {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let (dim_x, dim_y) = (map.dim_x, map.dim_y);
+
+        for y in 1..dim_y - 1 {
+            for x in 1..dim_x - 1 {
+                map[(x, y)] = if rng.roll_dice(1, 100) > 55 {
+                    Tile::Floor
+                } else {
+                    Tile::Wall
+                };
+            }
+        }
+        build_data.take_progress_snapshot();
+
+        for _ in 0..SMOOTHING_ITERATIONS {
+            let map = &mut build_data.map;
+            let mut smoothed = map.inner.clone();
+            for y in 1..dim_y - 1 {
+                for x in 1..dim_x - 1 {
+                    let walls = Self::wall_neighbours(map, x, y);
+                    let idx = map.coords_to_idx(x, y);
+                    smoothed[idx] = if walls > 4 || walls == 0 {
+                        Tile::Wall
+                    } else {
+                        Tile::Floor
+                    };
+                }
+            }
+            map.inner = smoothed;
+            build_data.take_progress_snapshot();
+        }
+
+        let map = &mut build_data.map;
+        let (mut x, y) = (dim_x / 2, dim_y / 2);
+        while map[(x, y)] != Tile::Floor && x > 1 {
+            x -= 1;
+        }
+        if map[(x, y)] != Tile::Floor {
+            // Nothing to the west of the center, carve a clearing instead
+            x = dim_x / 2;
+            for cy in y - 1..=y + 1 {
+                for cx in x - 1..=x + 1 {
+                    map[(cx, cy)] = Tile::Floor;
+                }
+            }
+        }
+        build_data.starting_position = Some(Position { x, y });
+    }
+
+    fn wall_neighbours(map: &Map, x: i32, y: i32) -> usize {
+        [
+            (x - 1, y - 1),
+            (x, y - 1),
+            (x + 1, y - 1),
+            (x - 1, y),
+            (x + 1, y),
+            (x - 1, y + 1),
+            (x, y + 1),
+            (x + 1, y + 1),
+        ]
+        .into_iter()
+        .filter(|&coords| map[coords] == Tile::Wall)
+        .count()
+    }
+}
+
+impl InitialMapBuilder for CaveBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data)
+    }
+}
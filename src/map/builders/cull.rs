@@ -0,0 +1,34 @@
+use super::{BuilderMap, MetaMapBuilder};
+use crate::map::{Map, Tile};
+use rltk::RandomNumberGenerator;
+
+/// Walls off every tile the player can't reach from their spawn
+pub struct CullUnreachable;
+
+impl CullUnreachable {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data
+            .starting_position
+            .expect("Culling requires a starting position");
+        let distances = distances_from(&mut build_data.map, (start.x, start.y));
+        for (idx, distance) in distances.into_iter().enumerate() {
+            if distance == f32::MAX {
+                build_data.map.inner[idx] = Tile::Wall;
+            }
+        }
+    }
+}
+
+/// Walking distance from `start` to every tile, `f32::MAX` for unreachable ones
+pub fn distances_from(map: &mut Map, start: (i32, i32)) -> Vec<f32> {
+    map.populate_passable();
+    let start = map.coords_to_idx(start.0, start.1);
+    rltk::DijkstraMap::new(map.dim_x, map.dim_y, &[start], map, 2000.0).map
+}
@@ -0,0 +1,48 @@
+use super::{BuilderMap, MetaMapBuilder};
+use crate::map::{Map, Tile};
+use rltk::RandomNumberGenerator;
+
+/// Puts doors where corridors enter rooms
+pub struct DoorPlacement;
+
+impl DoorPlacement {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn is_chokepoint(map: &Map, x: i32, y: i32) -> bool {
+        if x < 1 || x > map.dim_x - 2 || y < 1 || y > map.dim_y - 2 || map[(x, y)] != Tile::Floor {
+            return false;
+        }
+        let wall = |x, y| map[(x, y)] == Tile::Wall;
+        let floor = |x, y| map[(x, y)] != Tile::Wall;
+        (wall(x - 1, y) && wall(x + 1, y) && floor(x, y - 1) && floor(x, y + 1))
+            || (wall(x, y - 1) && wall(x, y + 1) && floor(x - 1, y) && floor(x + 1, y))
+    }
+
+    fn next_to_door(map: &Map, x: i32, y: i32) -> bool {
+        [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .any(|coords| map[coords] == Tile::Door)
+    }
+}
+
+impl MetaMapBuilder for DoorPlacement {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        for room in map.rooms.clone() {
+            let (x1, y1, x2, y2) = room.coords();
+            // The ring of tiles just outside of the room floor
+            let ring = (x1..=x2 + 1)
+                .flat_map(|x| [(x, y1), (x, y2 + 1)])
+                .chain((y1..=y2 + 1).flat_map(|y| [(x1, y), (x2 + 1, y)]))
+                .collect::<Vec<_>>();
+            for (x, y) in ring {
+                if Self::is_chokepoint(map, x, y) && !Self::next_to_door(map, x, y) {
+                    map[(x, y)] = Tile::Door;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::{BuilderMap, InitialMapBuilder};
+use crate::{components::Position, map::Tile};
+use rltk::RandomNumberGenerator;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiggerSpawn {
+    /// Every digger starts at the player spawn
+    StartingPoint,
+    /// Diggers start on a random already dug tile
+    Random,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrunkardSettings {
+    pub spawn: DiggerSpawn,
+    pub lifetime: i32,
+    pub floor_percent: f32,
+}
+
+impl DrunkardSettings {
+    pub fn open_area() -> Self {
+        Self {
+            spawn: DiggerSpawn::StartingPoint,
+            lifetime: 400,
+            floor_percent: 0.5,
+        }
+    }
+
+    pub fn open_halls() -> Self {
+        Self {
+            spawn: DiggerSpawn::Random,
+            lifetime: 400,
+            floor_percent: 0.5,
+        }
+    }
+
+    pub fn winding_passages() -> Self {
+        Self {
+            spawn: DiggerSpawn::Random,
+            lifetime: 100,
+            floor_percent: 0.4,
+        }
+    }
+
+    /// Shallow layers are wide open, deeper ones get more and more cramped
+    pub fn for_layer(layer: i32) -> Self {
+        match layer {
+            i32::MIN..=2 => Self::open_area(),
+            3..=5 => Self::open_halls(),
+            _ => Self::winding_passages(),
+        }
+    }
+}
+
+pub struct DrunkardsWalkBuilder {
+    settings: DrunkardSettings,
+}
+
+impl DrunkardsWalkBuilder {
+    pub fn new(layer: i32) -> Self {
+        Self::with_settings(DrunkardSettings::for_layer(layer))
+    }
+
+    pub fn with_settings(settings: DrunkardSettings) -> Self {
+        Self { settings }
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let (dim_x, dim_y) = (map.dim_x, map.dim_y);
+        let start = (dim_x / 2, dim_y / 2);
+        build_data.starting_position = Some(Position {
+            x: start.0,
+            y: start.1,
+        });
+        map[start] = Tile::Floor;
+
+        let total_tiles = ((dim_x - 2) * (dim_y - 2)) as usize;
+        let desired_floor = (total_tiles as f32 * self.settings.floor_percent) as usize;
+        let mut floor_tiles = vec![map.coords_to_idx(start.0, start.1)];
+
+        let mut diggers = 0;
+        while floor_tiles.len() < desired_floor {
+            let map = &mut build_data.map;
+            let (mut x, mut y) = match self.settings.spawn {
+                DiggerSpawn::StartingPoint => start,
+                DiggerSpawn::Random => {
+                    let idx = *rng.random_slice_entry(&floor_tiles).unwrap();
+                    map.idx_to_coords(idx)
+                }
+            };
+
+            for _ in 0..self.settings.lifetime {
+                if map[(x, y)] == Tile::Wall {
+                    map[(x, y)] = Tile::Floor;
+                    floor_tiles.push(map.coords_to_idx(x, y));
+                }
+                // Keep a margin so the map border stays solid
+                match rng.roll_dice(1, 4) {
+                    1 if x > 2 => x -= 1,
+                    2 if x < dim_x - 3 => x += 1,
+                    3 if y > 2 => y -= 1,
+                    4 if y < dim_y - 3 => y += 1,
+                    _ => {}
+                }
+            }
+
+            diggers += 1;
+            if diggers % 10 == 0 {
+                build_data.take_progress_snapshot();
+            }
+        }
+    }
+}
+
+impl InitialMapBuilder for DrunkardsWalkBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.build(rng, build_data)
+    }
+}
@@ -0,0 +1,36 @@
+use super::{BuilderMap, MetaMapBuilder};
+use crate::map::{Map, Tile};
+use rltk::RandomNumberGenerator;
+
+/// Knocks the corners off rectangular rooms
+pub struct RoomCornerRounder;
+
+impl RoomCornerRounder {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn fill_if_corner(map: &mut Map, x: i32, y: i32) {
+        let walls = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .filter(|&coords| map[coords] == Tile::Wall)
+            .count();
+        if walls == 2 {
+            map[(x, y)] = Tile::Wall;
+        }
+    }
+}
+
+impl MetaMapBuilder for RoomCornerRounder {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        for room in map.rooms.clone() {
+            let (x1, y1, x2, y2) = room.coords();
+            // Room floor spans from x1 + 1 to x2 inclusive
+            for (x, y) in [(x1 + 1, y1 + 1), (x2, y1 + 1), (x1 + 1, y2), (x2, y2)] {
+                Self::fill_if_corner(map, x, y);
+            }
+        }
+    }
+}
@@ -1,26 +1,20 @@
-use super::{spawn_room, MapBuilder};
+use super::{BuilderMap, InitialMapBuilder};
 use crate::{
     components::Position,
-    map::{Map, Rect, Tile},
+    map::{Rect, Tile},
 };
 use rltk::RandomNumberGenerator;
-use specs::prelude::*;
 
-pub struct SimpleMapBuilder {
-    map: Map,
-    snapshots: Vec<Map>,
-}
+pub struct SimpleMapBuilder;
 
 impl SimpleMapBuilder {
-    pub fn new(dim_x: i32, dim_y: i32, layer: i32) -> Self {
-        Self {
-            map: Map::new(dim_x, dim_y, layer),
-            snapshots: Vec::new(),
-        }
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
     }
 
-    fn simple_map(&mut self) {
-        let map = &mut self.map;
+    fn simple_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
         let dim_x = map.dim_x;
         let dim_y = map.dim_x;
 
@@ -33,8 +27,8 @@ impl SimpleMapBuilder {
             map[(dim_x - 1, y)] = Tile::Wall;
         }
 
-        let mut rng = rltk::RandomNumberGenerator::new();
         for _ in 0..dim_x / 4 {
+            let map = &mut build_data.map;
             // FIXME: Fix rooms sticking out of bounds
             let w = rng.range(5, 20);
             let h = rng.range(3, 10);
@@ -58,46 +52,19 @@ impl SimpleMapBuilder {
                 }
 
                 map.rooms.push(new_room);
+                build_data.take_progress_snapshot();
             }
         }
-        if rng.range(0, 2) != 0 {
-            let coords = rng
-                .random_slice_entry(&map.rooms[..map.rooms.len() - 1])
-                .unwrap()
-                .center();
-            map[coords] = Tile::TerminalService;
-        }
-        let coords = map.rooms.last().unwrap().center();
-        map[coords] = Tile::TerminalDown;
-        map.populate_passable();
+        let (x, y) = rng
+            .random_slice_entry(&build_data.map.rooms)
+            .unwrap()
+            .center();
+        build_data.starting_position = Some(Position { x, y });
     }
 }
 
-impl MapBuilder for SimpleMapBuilder {
-    fn build(&mut self, _rng: &mut RandomNumberGenerator) {
-        self.simple_map()
-    }
-
-    fn get_map(&self) -> Map {
-        self.map.clone()
-    }
-
-    fn get_player_spawn(&self, rng: &mut RandomNumberGenerator) -> Position {
-        let (x, y) = rng.random_slice_entry(&self.map.rooms).unwrap().center();
-        Position { x, y }
-    }
-
-    fn spawn(&mut self, ecs: &mut World) {
-        for room in &self.map.rooms {
-            spawn_room(&self.map, room, ecs)
-        }
-    }
-
-    fn take_snapshot(&mut self) {
-        //self.snapshots.push(self.map.as_ref().unwrap().clone())
-    }
-
-    fn get_snapshots(&self) -> Vec<Map> {
-        self.snapshots.clone()
+impl InitialMapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        self.simple_map(rng, build_data)
     }
 }
@@ -0,0 +1,72 @@
+use super::{cull::distances_from, BuilderMap, MetaMapBuilder};
+use crate::map::Tile;
+use rltk::RandomNumberGenerator;
+
+/// Places the down terminal as far from the player as possible
+/// and the service terminal somewhere in the far half of the map,
+/// every layer but the first gets an up terminal at the player spawn
+pub struct PlaceTerminals;
+
+impl PlaceTerminals {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MetaMapBuilder for PlaceTerminals {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let start = build_data
+            .starting_position
+            .expect("Placing terminals requires a starting position");
+        let map = &mut build_data.map;
+        let distances = distances_from(map, (start.x, start.y));
+        let reachable = |candidates: Vec<usize>| {
+            candidates
+                .into_iter()
+                .filter(|&idx| distances[idx] < f32::MAX && distances[idx] > 0.)
+                .collect::<Vec<_>>()
+        };
+        let floor = (0..map.size())
+            .filter(|&idx| map.inner[idx] == Tile::Floor)
+            .collect::<Vec<_>>();
+
+        // Terminals go into room centers when there are rooms to put them in
+        let mut candidates = reachable(
+            map.rooms
+                .iter()
+                .map(|room| {
+                    let (x, y) = room.center();
+                    map.coords_to_idx(x, y)
+                })
+                .collect(),
+        );
+        if candidates.is_empty() {
+            candidates = reachable(floor);
+        }
+
+        if map.layer > 0 {
+            let idx = map.coords_to_idx(start.x, start.y);
+            map.inner[idx] = Tile::TerminalUp;
+        }
+
+        // A layer with no floor away from the start gets no terminals at all
+        let Some(exit) = candidates
+            .iter()
+            .copied()
+            .max_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+        else {
+            return;
+        };
+        map.inner[exit] = Tile::TerminalDown;
+
+        let max_distance = distances[exit];
+        let service = candidates
+            .into_iter()
+            .filter(|&idx| idx != exit && distances[idx] > max_distance / 2.)
+            .collect::<Vec<_>>();
+        if let Some(&idx) = rng.random_slice_entry(&service) {
+            map.inner[idx] = Tile::TerminalService;
+        }
+    }
+}
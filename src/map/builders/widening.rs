@@ -0,0 +1,36 @@
+use super::{BuilderMap, MetaMapBuilder};
+use crate::map::Tile;
+use rltk::RandomNumberGenerator;
+
+/// Makes every passage outside of rooms two tiles wide
+pub struct CorridorWidener;
+
+impl CorridorWidener {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl MetaMapBuilder for CorridorWidener {
+    fn build_map(&mut self, _rng: &mut RandomNumberGenerator, build_data: &mut BuilderMap) {
+        let map = &mut build_data.map;
+        let in_room = |x: i32, y: i32| {
+            map.rooms
+                .iter()
+                .any(|r| x > r.x1() && x <= r.x2() && y > r.y1() && y <= r.y2())
+        };
+        let corridors = (0..map.size())
+            .map(|idx| map.idx_to_coords(idx))
+            .filter(|&(x, y)| map[(x, y)] == Tile::Floor && !in_room(x, y))
+            .collect::<Vec<_>>();
+
+        for (x, y) in corridors {
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < map.dim_x - 1 && ny < map.dim_y - 1 && map[(nx, ny)] == Tile::Wall {
+                    map[(nx, ny)] = Tile::Floor;
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::components::*;
 use crate::gui::GameLog;
 use crate::map::Tile;
+use crate::systems::noise::{self, NoiseQueue};
+use crate::systems::ranged_combat::ranged_weapon_of;
 use crate::{map::Map, state::RunState};
-use rltk::{Rltk, VirtualKeyCode};
+use rltk::{RandomNumberGenerator, Rltk, VirtualKeyCode};
 use serde::{Deserialize, Serialize};
 #[allow(deprecated)]
 use specs::error::NoError;
@@ -22,17 +24,19 @@ pub fn player_input(ecs: &mut World, ctx: &mut Rltk) -> RunState {
     match ctx.key {
         None => return RunState::AwaitingInput,
         Some(key) => match key {
-            A => try_move_player(ecs, -1, 0),
-            D => try_move_player(ecs, 1, 0),
-            W => try_move_player(ecs, 0, -1),
-            S => try_move_player(ecs, 0, 1),
-            Q => try_move_player(ecs, -1, -1),
-            E => try_move_player(ecs, 1, -1),
-            Z => try_move_player(ecs, -1, 1),
-            X => try_move_player(ecs, 1, 1),
+            A => step_player(ecs, -1, 0, ctx.shift),
+            D => step_player(ecs, 1, 0, ctx.shift),
+            W => step_player(ecs, 0, -1, ctx.shift),
+            S => step_player(ecs, 0, 1, ctx.shift),
+            Q => step_player(ecs, -1, -1, ctx.shift),
+            E => step_player(ecs, 1, -1, ctx.shift),
+            Z => step_player(ecs, -1, 1, ctx.shift),
+            X => step_player(ecs, 1, 1, ctx.shift),
             G => get_item(ecs),
             R => return try_interact(ecs),
             I => return RunState::ShowInventory,
+            H => return RunState::ShowAbilities,
+            F => return try_shoot(ecs),
             Escape => return RunState::MainMenu(crate::gui::MainMenuSelection::SaveGame),
             Space => return RunState::PlayerTurn,
             _ => return RunState::AwaitingInput,
@@ -47,12 +51,31 @@ fn try_interact(ecs: &mut World) -> RunState {
 
     match map[(player.position.x, player.position.y)] {
         Tile::TerminalDown => RunState::NextLayer,
-        Tile::TerminalUp => todo!(),
-        Tile::TerminalService => RunState::RevealMap(0),
+        Tile::TerminalUp => RunState::PreviousLayer,
+        Tile::TerminalService => RunState::ServiceTerminal,
         _ => RunState::AwaitingInput,
     }
 }
 
+fn try_shoot(ecs: &mut World) -> RunState {
+    let player = ecs.fetch::<Player>();
+    let weapon = ranged_weapon_of(
+        player.entity,
+        &ecs.read_storage::<RangedWeapon>(),
+        &ecs.read_storage::<Equipped>(),
+    );
+    match weapon {
+        Some(weapon) => RunState::ShootTargeting {
+            range: weapon.range,
+        },
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entry("You have nothing to shoot with".into());
+            RunState::AwaitingInput
+        }
+    }
+}
+
 fn get_item(ecs: &mut World) {
     let player = ecs.fetch::<Player>();
     let entities = ecs.entities();
@@ -83,7 +106,35 @@ fn get_item(ecs: &mut World) {
     }
 }
 
-fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
+/// Running covers two tiles in a turn, but everything nearby hears it
+fn step_player(ecs: &mut World, delta_x: i32, delta_y: i32, run: bool) {
+    let (delta_x, delta_y) = if ecs
+        .read_storage::<Confused>()
+        .contains(ecs.fetch::<Player>().entity)
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        ecs.fetch_mut::<GameLog>()
+            .entry("You stumble around in confusion".into());
+        (rng.range(-1, 2), rng.range(-1, 2))
+    } else {
+        (delta_x, delta_y)
+    };
+    // Only the first step can turn into an attack, a run stops short of whatever is in the way
+    if try_move_player(ecs, delta_x, delta_y, true)
+        && run
+        && try_move_player(ecs, delta_x, delta_y, false)
+    {
+        let player = ecs.fetch::<Player>();
+        ecs.fetch_mut::<NoiseQueue>().make(
+            player.position.x,
+            player.position.y,
+            noise::RUNNING_VOLUME,
+        );
+    }
+}
+
+/// Moves or, if `attack` is set, attacks, returns whether the player actually moved
+fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32, attack: bool) -> bool {
     let mut positions = ecs.write_storage::<Position>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let mut controls = ecs.write_storage::<Control>();
@@ -92,20 +143,23 @@ fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
     let mut melee = ecs.write_storage::<MeleeAttack>();
     let map = ecs.fetch::<Map>();
 
+    let mut moved = false;
     for (_, pos, vis) in (&mut controls, &mut positions, &mut viewsheds).join() {
         let x = min(map.dim_x - 1, max(0, pos.x + delta_x));
         let y = min(map.dim_y - 1, max(0, pos.y + delta_y));
         for maybe_target in map.tile_content[map.coords_to_idx(x, y)].iter() {
             if let Some(_t) = stats.get(*maybe_target) {
-                melee
-                    .insert(
-                        player.entity,
-                        MeleeAttack {
-                            target: *maybe_target,
-                        },
-                    )
-                    .expect("Can't insert Melee");
-                return;
+                if attack {
+                    melee
+                        .insert(
+                            player.entity,
+                            MeleeAttack {
+                                target: *maybe_target,
+                            },
+                        )
+                        .expect("Can't insert Melee");
+                }
+                return false;
             }
         }
         if map.passable[map.coords_to_idx(x, y)] {
@@ -113,7 +167,9 @@ fn try_move_player(ecs: &mut World, delta_x: i32, delta_y: i32) {
             pos.y = y;
             player.position.x = x;
             player.position.y = y;
+            moved = true;
         }
         vis.dirty = true;
     }
+    moved
 }
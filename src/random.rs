@@ -1,29 +1,28 @@
-use crate::map::builders::{bsp::BspBuilder, simple::SimpleMapBuilder, MapBuilder};
+use crate::map::builders::{
+    bsp::BspBuilder, cave::CaveBuilder, cull::CullUnreachable, doors::DoorPlacement,
+    drunkard::DrunkardsWalkBuilder, rounding::RoomCornerRounder, simple::SimpleMapBuilder,
+    terminals::PlaceTerminals, widening::CorridorWidener, BuilderChain, InitialMapBuilder,
+};
 use rltk::RandomNumberGenerator;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 
-#[derive(Eq, Debug, Clone, Serialize, Deserialize)]
-pub struct Entry {
-    name: String,
-    weight: i32,
-}
-
-impl PartialEq for Entry {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Outcome {
+    Name(String),
+    Table(Table),
+    Nothing,
 }
 
-impl std::hash::Hash for Entry {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    outcome: Outcome,
+    weight: i32,
 }
 
+/// Weighted table, entries are rolled in insertion order so results only depend on the rng
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Table {
-    entries: HashSet<Entry>,
+    entries: Vec<Entry>,
     total_weight: i32,
 }
 
@@ -35,36 +34,62 @@ impl Table {
         }
     }
 
+    /// Adds a named entry, inserting the same name twice adds up the weights
     pub fn insert<S: Into<String>>(mut self, name: S, weight: i32) -> Self {
-        if weight > 0 {
-            let entry = Entry {
-                name: name.into(),
-                weight,
-            };
-            if self.entries.insert(entry) {
+        let name = name.into();
+        let existing = self.entries.iter_mut().find(|e| match &e.outcome {
+            Outcome::Name(n) => *n == name,
+            _ => false,
+        });
+        match existing {
+            Some(entry) if weight > 0 => {
+                entry.weight += weight;
                 self.total_weight += weight;
+                self
             }
+            Some(_) => self,
+            None => self.push(Outcome::Name(name), weight),
+        }
+    }
+
+    /// Adds a sub-table that is rolled in turn when this entry comes up
+    pub fn insert_table(self, table: Table, weight: i32) -> Self {
+        self.push(Outcome::Table(table), weight)
+    }
+
+    /// Adds a chance for the roll to produce nothing at all
+    pub fn insert_nothing(self, weight: i32) -> Self {
+        self.push(Outcome::Nothing, weight)
+    }
+
+    fn push(mut self, outcome: Outcome, weight: i32) -> Self {
+        if weight > 0 {
+            self.entries.push(Entry { outcome, weight });
+            self.total_weight += weight;
         }
         self
     }
 
-    pub fn roll<'s, 'r>(&'s self, rng: &'r mut RandomNumberGenerator) -> Option<&'s str> {
-        if self.total_weight == 0 {
+    pub fn is_empty(&self) -> bool {
+        self.total_weight == 0
+    }
+
+    pub fn roll<'s>(&'s self, rng: &mut RandomNumberGenerator) -> Option<&'s str> {
+        if self.total_weight <= 0 {
             return None;
         }
-        let mut entries = self.entries.iter();
-        // Safe: we have just asserted that entries is not empty
-        let mut entry = entries.next().unwrap();
         let mut roll = rng.roll_dice(1, self.total_weight) - 1;
-        while roll > 0 {
+        for entry in self.entries.iter() {
             if roll < entry.weight {
-                return Some(&entry.name);
+                return match &entry.outcome {
+                    Outcome::Name(name) => Some(name),
+                    Outcome::Table(table) => table.roll(rng),
+                    Outcome::Nothing => None,
+                };
             }
-
             roll -= entry.weight;
-            entry = entries.next()?
         }
-        None
+        unreachable!("Roll exceeded the total weight of the table")
     }
 }
 
@@ -74,10 +99,105 @@ impl Default for Table {
     }
 }
 
-pub fn random_map_builder(dim_x: i32, dim_y: i32, layer: i32) -> Box<dyn MapBuilder> {
-    let mut rng = RandomNumberGenerator::seeded(69);
-    match rng.roll_dice(1, 2) {
-        1 => Box::new(SimpleMapBuilder::new(dim_x, dim_y, layer)),
-        _ => Box::new(BspBuilder::new(dim_x, dim_y, layer)),
+/// Seed of the whole run, every layer is generated from it
+#[derive(Debug, Clone, Copy)]
+pub struct RunSeed(pub u64);
+
+/// Derives the generator for a single layer from the run seed
+pub fn layer_rng(seed: u64, layer: i32) -> RandomNumberGenerator {
+    // splitmix64 finalizer, so neighbouring layers get unrelated streams
+    let mut z = seed ^ (layer as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    RandomNumberGenerator::seeded(z ^ (z >> 31))
+}
+
+pub fn random_map_builder(
+    dim_x: i32,
+    dim_y: i32,
+    layer: i32,
+    rng: &mut RandomNumberGenerator,
+) -> BuilderChain {
+    let (starter, has_rooms): (Box<dyn InitialMapBuilder>, bool) = match rng.roll_dice(1, 4) {
+        1 => (Box::new(SimpleMapBuilder::new()), true),
+        2 => (Box::new(CaveBuilder::new()), false),
+        3 => (Box::new(DrunkardsWalkBuilder::new(layer)), false),
+        _ => (Box::new(BspBuilder::new()), true),
+    };
+    let mut chain = BuilderChain::new(dim_x, dim_y, layer).start_with(starter);
+
+    if has_rooms {
+        if rng.roll_dice(1, 2) == 1 {
+            chain = chain.with(Box::new(RoomCornerRounder::new()));
+        }
+        // Doors need single-tile chokepoints, so they don't mix with wide corridors
+        match rng.roll_dice(1, 3) {
+            1 => chain = chain.with(Box::new(CorridorWidener::new())),
+            2 => chain = chain.with(Box::new(DoorPlacement::new())),
+            _ => {}
+        }
+    }
+
+    chain
+        .with(Box::new(CullUnreachable::new()))
+        .with(Box::new(PlaceTerminals::new()))
+}
+
+#[test]
+fn same_seed_same_layer() {
+    let raws = crate::raws::RawMaster::load(crate::raws::RAWS_PATH).unwrap();
+    let generate = |seed, layer| {
+        let mut rng = layer_rng(seed, layer);
+        let mut builder = random_map_builder(128, 128, layer, &mut rng);
+        builder.build(&mut rng, &raws);
+        (
+            builder.get_map(),
+            builder.get_player_spawn(),
+            builder.get_spawn_list().to_vec(),
+            builder.get_packs().to_vec(),
+        )
+    };
+    for layer in 0..4 {
+        assert_eq!(generate(42, layer), generate(42, layer));
+    }
+    assert_ne!(generate(1, 0).0, generate(2, 0).0);
+}
+
+#[test]
+fn table_distribution() {
+    let table = Table::new().insert("a", 1).insert("b", 3).insert("c", 6);
+    let freq = roll_frequencies(&table, 100_000);
+    assert!(!freq.contains_key(&None));
+    assert!((freq[&Some("a")] - 0.1).abs() < 0.01);
+    assert!((freq[&Some("b")] - 0.3).abs() < 0.01);
+    assert!((freq[&Some("c")] - 0.6).abs() < 0.01);
+}
+
+#[test]
+fn table_single_entry_always_rolls() {
+    let table = Table::new().insert("only", 1);
+    let mut rng = RandomNumberGenerator::seeded(7);
+    assert!((0..1000).all(|_| table.roll(&mut rng) == Some("only")));
+    assert_eq!(Table::new().roll(&mut rng), None);
+}
+
+#[test]
+fn table_nested_and_nothing() {
+    let loot = Table::new().insert("x", 1).insert("y", 1);
+    let table = Table::new().insert_table(loot, 2).insert_nothing(2);
+    let freq = roll_frequencies(&table, 100_000);
+    assert!((freq[&None] - 0.5).abs() < 0.01);
+    assert!((freq[&Some("x")] - 0.25).abs() < 0.01);
+    assert!((freq[&Some("y")] - 0.25).abs() < 0.01);
+}
+
+/// Relative frequency of every outcome over `rolls` rolls of a fixed seed
+#[cfg(test)]
+fn roll_frequencies(table: &Table, rolls: usize) -> std::collections::HashMap<Option<&str>, f32> {
+    let mut rng = RandomNumberGenerator::seeded(1337);
+    let mut counts = std::collections::HashMap::new();
+    for _ in 0..rolls {
+        *counts.entry(table.roll(&mut rng)).or_insert(0.0) += 1.0 / rolls as f32;
     }
+    counts
 }
@@ -0,0 +1,410 @@
+use crate::{components::*, random};
+use rltk::RGB;
+use serde::Deserialize;
+use specs::{
+    prelude::*,
+    saveload::{MarkedBuilder, SimpleMarker},
+};
+use std::{collections::HashMap, fmt, path::Path};
+
+pub const RAWS_PATH: &str = "./resources/raws.json";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderableRaw {
+    pub glyph: String,
+    pub fg: String,
+    pub bg: String,
+    pub order: u8,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsRaw {
+    pub power: i32,
+    pub hp: i32,
+    pub defense: i32,
+    pub compute: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DropRaw {
+    /// A drop without a name is the chance of dropping nothing
+    pub name: Option<String>,
+    pub weight: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InflictsRaw {
+    pub status: StatusEffect,
+    #[serde(default = "always")]
+    pub chance: i32,
+}
+
+/// How many of a mob spawn together
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackRaw {
+    pub min: i32,
+    pub max: i32,
+}
+
+fn always() -> i32 {
+    100
+}
+
+/// A template for a mob or an item, every field but `name` is an optional component
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityRaw {
+    pub name: String,
+    pub renderable: Option<RenderableRaw>,
+    #[serde(default)]
+    pub enemy: bool,
+    pub faction: Option<Faction>,
+    pub pack: Option<PackRaw>,
+    #[serde(default)]
+    pub hunter: bool,
+    #[serde(default)]
+    pub blocker: bool,
+    pub viewshed: Option<i32>,
+    pub speed: Option<i32>,
+    pub stats: Option<StatsRaw>,
+    #[serde(default)]
+    pub inventory: bool,
+    #[serde(default)]
+    pub item: bool,
+    #[serde(default)]
+    pub consumable: bool,
+    pub effect: Option<Effect>,
+    pub slot: Option<Slot>,
+    pub equip_bonus: Option<EquipBonus>,
+    pub level_up: Option<i32>,
+    pub drops: Option<Vec<DropRaw>>,
+    pub inflicts: Option<InflictsRaw>,
+    pub resistances: Option<HashMap<DamageType, i32>>,
+    pub attack_type: Option<DamageType>,
+    pub ranged: Option<RangedWeapon>,
+    pub xp: Option<i32>,
+}
+
+/// How often an entity spawns, `weight` grows by `per_layer` every layer past `min_layer`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnTableEntry {
+    pub name: String,
+    pub weight: i32,
+    #[serde(default)]
+    pub per_layer: i32,
+    #[serde(default)]
+    pub min_layer: i32,
+    pub max_layer: Option<i32>,
+    pub min_weight: Option<i32>,
+    pub max_weight: Option<i32>,
+}
+
+impl SpawnTableEntry {
+    pub fn weight_at(&self, layer: i32) -> i32 {
+        if layer < self.min_layer || self.max_layer.is_some_and(|max| layer > max) {
+            return 0;
+        }
+        let weight = self.weight + self.per_layer * (layer - self.min_layer);
+        let weight = self.min_weight.map_or(weight, |min| i32::max(weight, min));
+        let weight = self.max_weight.map_or(weight, |max| i32::min(weight, max));
+        i32::max(weight, 0)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawsFile {
+    entities: Vec<EntityRaw>,
+    #[serde(default)]
+    spawn_table: Vec<SpawnTableEntry>,
+}
+
+#[derive(Debug)]
+pub enum RawsError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    Duplicate(String),
+    Invalid { entity: String, reason: String },
+    UnknownSpawn(String),
+}
+
+impl fmt::Display for RawsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawsError::Io(e) => write!(f, "Could not read raws: {}", e),
+            RawsError::Parse(e) => write!(f, "Could not parse raws: {}", e),
+            RawsError::Duplicate(name) => write!(f, "Entity {} is defined more than once", name),
+            RawsError::Invalid { entity, reason } => {
+                write!(f, "Entity {} is invalid: {}", entity, reason)
+            }
+            RawsError::UnknownSpawn(name) => {
+                write!(f, "Spawn table refers to unknown entity {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RawsError {}
+
+/// Every entity template, indexed by name
+#[derive(Debug, Default)]
+pub struct RawMaster {
+    entities: HashMap<String, EntityRaw>,
+    spawn_table: Vec<SpawnTableEntry>,
+}
+
+impl RawMaster {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RawsError> {
+        let data = std::fs::read_to_string(path).map_err(RawsError::Io)?;
+        Self::from_json(&data)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, RawsError> {
+        let file: RawsFile = serde_json::from_str(data).map_err(RawsError::Parse)?;
+        let mut entities = HashMap::new();
+        for entity in file.entities {
+            validate(&entity)?;
+            let name = entity.name.clone();
+            if entities.insert(name.clone(), entity).is_some() {
+                return Err(RawsError::Duplicate(name));
+            }
+        }
+        for entity in entities.values() {
+            for drop in entity.drops.iter().flatten() {
+                if let Some(name) = &drop.name {
+                    if !entities.contains_key(name) {
+                        return Err(RawsError::Invalid {
+                            entity: entity.name.clone(),
+                            reason: format!("drops unknown entity {}", name),
+                        });
+                    }
+                }
+            }
+        }
+        for spawn in file.spawn_table.iter() {
+            if !entities.contains_key(&spawn.name) {
+                return Err(RawsError::UnknownSpawn(spawn.name.clone()));
+            }
+        }
+        Ok(Self {
+            entities,
+            spawn_table: file.spawn_table,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EntityRaw> {
+        self.entities.get(name)
+    }
+
+    /// Effective spawn weights on a layer, in the order they are declared
+    pub fn spawn_weights(&self, layer: i32) -> Vec<(&str, i32)> {
+        self.spawn_table
+            .iter()
+            .map(|spawn| (&*spawn.name, spawn.weight_at(layer)))
+            .collect()
+    }
+
+    pub fn spawn_table(&self, layer: i32) -> random::Table {
+        self.spawn_weights(layer)
+            .into_iter()
+            .fold(random::Table::new(), |table, (name, weight)| {
+                table.insert(name, weight)
+            })
+    }
+}
+
+fn validate(entity: &EntityRaw) -> Result<(), RawsError> {
+    let invalid = |reason: &str| RawsError::Invalid {
+        entity: entity.name.clone(),
+        reason: reason.into(),
+    };
+    if let Some(renderable) = &entity.renderable {
+        if renderable.glyph.chars().count() != 1 {
+            return Err(invalid("glyph must be a single character"));
+        }
+        if RGB::from_hex(&renderable.fg).is_err() || RGB::from_hex(&renderable.bg).is_err() {
+            return Err(invalid("colors must be #RRGGBB"));
+        }
+    }
+    if entity.item && entity.ranged.is_some() && entity.slot.is_none() {
+        return Err(invalid("ranged weapon items need a slot"));
+    }
+    if !entity.item && (entity.consumable || entity.slot.is_some()) {
+        return Err(invalid("only items can be consumable or equippable"));
+    }
+    if entity
+        .pack
+        .as_ref()
+        .is_some_and(|pack| pack.min < 1 || pack.max < pack.min)
+    {
+        return Err(invalid(
+            "pack size must be at least 1 and max no less than min",
+        ));
+    }
+    if entity.enemy && entity.faction.is_none() {
+        return Err(invalid("enemies need a faction"));
+    }
+    if entity.equip_bonus.is_some() && entity.slot.is_none() {
+        return Err(invalid("equip bonus requires a slot"));
+    }
+    if entity.xp.is_some() && entity.stats.is_none() {
+        return Err(invalid("only things with stats can be killed for xp"));
+    }
+    if entity.stats.is_some() && entity.item {
+        return Err(invalid("items can't have stats"));
+    }
+    if entity.speed.is_some_and(|speed| speed <= 0) {
+        return Err(invalid("speed must be positive"));
+    }
+    Ok(())
+}
+
+/// Spawns an entity from its template, returns `None` for unknown names
+pub fn spawn_named(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    let raw = ecs.fetch::<RawMaster>().get(name)?.clone();
+
+    let mut builder = ecs.create_entity().with(Position { x, y }).with(Name {
+        name: raw.name.clone(),
+    });
+
+    if let Some(renderable) = &raw.renderable {
+        builder = builder.with(Renderable {
+            glyph: rltk::to_cp437(renderable.glyph.chars().next().unwrap()),
+            fg: RGB::from_hex(&renderable.fg).unwrap(),
+            bg: RGB::from_hex(&renderable.bg).unwrap(),
+            render_order: renderable.order,
+        });
+    }
+    if raw.enemy {
+        builder = builder.with(Enemy).with(AiState::default());
+    }
+    if let Some(faction) = raw.faction {
+        builder = builder.with(faction);
+    }
+    if raw.hunter {
+        builder = builder.with(Hunter);
+    }
+    if raw.blocker {
+        builder = builder.with(Blocker);
+    }
+    if let Some(range) = raw.viewshed {
+        builder = builder.with(Viewshed {
+            visible_tiles: Default::default(),
+            range,
+            dirty: true,
+        });
+    }
+    if let Some(speed) = raw.speed {
+        builder = builder.with(Initiative { speed, energy: 0 });
+    }
+    if let Some(stats) = &raw.stats {
+        builder = builder.with(Stats {
+            base_power: stats.power,
+            base_hp: stats.hp,
+            hp: stats.hp,
+            base_defense: stats.defense,
+            compute: stats.compute,
+            base_compute: stats.compute,
+        });
+    }
+    if raw.inventory {
+        builder = builder.with(HasInventory);
+    }
+    if raw.item {
+        builder = builder.with(Item);
+    }
+    if raw.consumable {
+        builder = builder.with(Consumable);
+    }
+    if let Some(effect) = raw.effect {
+        builder = builder.with(effect);
+    }
+    if let Some(slot) = raw.slot {
+        builder = builder.with(Equippable { slot });
+    }
+    if let Some(bonus) = raw.equip_bonus {
+        builder = builder.with(bonus);
+    }
+    if let Some(amount) = raw.level_up {
+        builder = builder.with(LevelUp { amount });
+    }
+    if let Some(percent) = raw.resistances {
+        builder = builder.with(Resistances { percent });
+    }
+    if let Some(xp) = raw.xp {
+        builder = builder.with(XpValue { xp });
+    }
+    if let Some(weapon) = raw.ranged {
+        builder = builder.with(weapon);
+    }
+    if let Some(kind) = raw.attack_type {
+        builder = builder.with(NaturalAttack { kind });
+    }
+    if let Some(inflicts) = raw.inflicts {
+        builder = builder.with(Inflicts {
+            status: inflicts.status,
+            chance: inflicts.chance,
+        });
+    }
+    if let Some(drops) = raw.drops {
+        let table = drops
+            .into_iter()
+            .fold(random::Table::new(), |table, d| match d.name {
+                Some(name) => table.insert(name, d.weight),
+                None => table.insert_nothing(d.weight),
+            });
+        builder = builder.with(Drops { table });
+    }
+
+    Some(builder.marked::<SimpleMarker<SerializeMe>>().build())
+}
+
+#[test]
+fn shipped_raws_are_valid() {
+    RawMaster::load(RAWS_PATH).unwrap();
+}
+
+#[test]
+fn unknown_component_is_rejected() {
+    let raws = r#"{ "entities": [ { "name": "Skel", "jetpack": true } ] }"#;
+    assert!(matches!(
+        RawMaster::from_json(raws),
+        Err(RawsError::Parse(_))
+    ));
+}
+
+#[test]
+fn unknown_drop_is_rejected() {
+    let raws =
+        r#"{ "entities": [ { "name": "Skel", "drops": [ { "name": "Gold", "weight": 1 } ] } ] }"#;
+    assert!(matches!(
+        RawMaster::from_json(raws),
+        Err(RawsError::Invalid { .. })
+    ));
+}
+
+#[test]
+fn spawn_weights_follow_layer() {
+    let raws = RawMaster::load(RAWS_PATH).unwrap();
+    let weight = |name, layer| {
+        raws.spawn_weights(layer)
+            .into_iter()
+            .find(|&(n, _)| n == name)
+            .unwrap()
+            .1
+    };
+    assert_eq!(weight("Skel", 0), 0);
+    assert_eq!(weight("Skel", 4), 4);
+    assert_eq!(weight("Skel", 30), 10);
+    assert_eq!(weight("Snake", 0), 10);
+    assert_eq!(weight("Snake", 30), 2);
+    for layer in 0..50 {
+        assert!(raws.spawn_weights(layer).iter().all(|&(_, w)| w >= 0));
+    }
+}
@@ -1,26 +1,18 @@
-use std::{cmp::min, collections::HashSet};
+use std::collections::HashSet;
 
-use crate::{components::*, player::Player, random};
-use rltk::RGB;
+use crate::{
+    components::*,
+    map::{Map, Tile},
+    player::Player,
+};
+use rltk::{DistanceAlg, Point, RandomNumberGenerator, RGB};
 use specs::{
     prelude::*,
     saveload::{MarkedBuilder, SimpleMarker},
 };
-pub fn room_table(layer: i32) -> random::Table {
-    random::Table::new()
-        .insert("Skel", min(layer, 10))
-        .insert("Snake", 10 - layer)
-        .insert("Healing cell", 3)
-        .insert("Laser cell", 3)
-        .insert("Compact missile", 2)
-        .insert("Energy Shield", 2)
-        .insert("Vibro Blade", 2)
-        .insert("Memory Shard", 1)
-        .insert("Energy Cell", 2)
-}
-
 pub fn player(ecs: &mut World) -> Player {
     let mut slots = HashSet::new();
+    slots.insert(Slot::Head);
     slots.insert(Slot::Body);
     slots.insert(Slot::Hands);
     let entity = ecs
@@ -33,6 +25,7 @@ pub fn player(ecs: &mut World) -> Player {
             render_order: 1,
         })
         .with(Control)
+        .with(Faction::Player)
         .with(Viewshed {
             visible_tiles: Default::default(),
             range: 8,
@@ -52,7 +45,29 @@ pub fn player(ecs: &mut World) -> Player {
         })
         .with(HasInventory)
         .with(Slots { slots })
-        .with(TraceTimer { timer: 400 })
+        .with(TraceTimer {
+            timer: 400,
+            alert: 0,
+            pending_hunters: 0,
+            spoofed: 0,
+        })
+        .with(Experience {
+            level: 1,
+            xp: 0,
+            pending: 0,
+        })
+        .with(Initiative {
+            speed: 100,
+            energy: 0,
+        })
+        .with(KnownAbilities {
+            abilities: vec![
+                Ability::Overload,
+                Ability::Blind,
+                Ability::Scramble,
+                Ability::Ping,
+            ],
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
     Player {
@@ -61,204 +76,32 @@ pub fn player(ecs: &mut World) -> Player {
     }
 }
 
-pub fn snake(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('&'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Viewshed {
-            visible_tiles: Default::default(),
-            range: 8,
-            dirty: true,
-        })
-        .with(Enemy)
-        .with(Name {
-            name: "Snake".to_string(),
-        })
-        .with(Blocker)
-        .with(Stats {
-            base_power: 3,
-            base_hp: 5,
-            hp: 5,
-            base_defense: 1,
-            compute: 0,
-            base_compute: 0,
-        })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn skel(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('$'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2,
-        })
-        .with(Viewshed {
-            visible_tiles: Default::default(),
-            range: 8,
-            dirty: true,
-        })
-        .with(Enemy)
-        .with(Name {
-            name: "Skel".to_string(),
-        })
-        .with(Blocker)
-        .with(Stats {
-            base_power: 8,
-            base_hp: 10,
-            hp: 10,
-            base_defense: 3,
-            compute: 0,
-            base_compute: 0,
-        })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn healing_cell(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437(';'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Healing cell".to_string(),
-        })
-        .with(Item)
-        .with(Consumable)
-        .with(Effect::HealSelf(10))
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn laser_cell(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('\''),
-            fg: RGB::named(rltk::GREEN),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Laser Cell".to_string(),
-        })
-        .with(Item)
-        .with(Consumable)
-        .with(Effect::DamageRanged {
-            range: 5,
-            damage: 10,
-        })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn compact_missile(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('>'),
-            fg: RGB::named(rltk::GREEN4),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Compact Missile".to_string(),
-        })
-        .with(Item)
-        .with(Consumable)
-        .with(Effect::DamageAOE {
-            range: 5,
-            damage: 10,
-            radius: 3,
-        })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn energy_shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('0'),
-            fg: RGB::named(rltk::SKYBLUE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Energy Shield".to_string(),
-        })
-        .with(Item)
-        .with(Equippable { slot: Slot::Body })
-        .with(EquipBonus::Defense(5))
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn vibro_blade(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('/'),
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Vibro Blade".to_string(),
-        })
-        .with(Item)
-        .with(Equippable { slot: Slot::Hands })
-        .with(EquipBonus::Attack(5))
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn memory_shard(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('σ'),
-            fg: RGB::named(rltk::PURPLE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Memory Shard".to_string(),
-        })
-        .with(Item)
-        .with(Consumable)
-        .with(LevelUp { amount: 5 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
-}
-
-pub fn energy_cell(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph: rltk::to_cp437('~'),
-            fg: RGB::named(rltk::PURPLE),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 0,
-        })
-        .with(Name {
-            name: "Energy Cell".to_string(),
-        })
-        .with(Item)
-        .with(Consumable)
-        .with(Effect::Recharge(5))
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// A free floor tile just out of the player's sight, where hunters come in from
+pub fn edge_of_vision(
+    map: &Map,
+    player: Point,
+    range: i32,
+    rng: &mut RandomNumberGenerator,
+) -> Option<Point> {
+    let candidates = |min: f32, max: f32| {
+        (0..map.dim_x)
+            .flat_map(|x| (0..map.dim_y).map(move |y| Point::new(x, y)))
+            .filter(|p| {
+                let idx = map.coords_to_idx(p.x, p.y);
+                map[(p.x, p.y)] == Tile::Floor && map.passable[idx] && !map.visible[idx]
+            })
+            .filter(|&p| {
+                let distance = DistanceAlg::Pythagoras.distance2d(p, player);
+                distance > min && distance <= max
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut spots = candidates(range as f32, (range + 4) as f32);
+    if spots.is_empty() {
+        spots = candidates(0.0, (range * 2) as f32);
+    }
+    if spots.is_empty() {
+        return None;
+    }
+    Some(spots[rng.range(0, spots.len())])
 }
@@ -1,24 +1,34 @@
 use crate::{
     camera,
     components::*,
+    dungeon::{self, MasterDungeon},
     game_save::{load_game, save_game},
     gui::{
-        draw_ui, show_inventory, show_levelup, show_main_menu, show_targeting, GameLog,
-        MainMenuSelection, TargetingResult,
+        draw_ui, show_abilities, show_inventory, show_levelup, show_main_menu, show_targeting,
+        show_terminal, AbilityMenuResult, GameLog, MainMenuSelection, TargetingResult,
+        TerminalAction, TerminalMenuResult,
     },
-    map::Map,
+    map::{Map, Tile},
     player::{player_input, Player},
-    random::random_map_builder,
+    random::{layer_rng, random_map_builder, RunSeed},
+    raws::{self, RawMaster},
     spawner,
     systems::{
         ai::EnemyAI,
+        hacking::HackingSystem,
+        initiative::InitiativeSystem,
         inventory_system::{ItemCollectionSystem, ItemConsumptionSystem},
         map_system::MapSystem,
         melee_combat::{DamageSystem, MeleeCombatSystem},
+        navigation::{Navigation, NavigationSystem},
+        noise::NoiseSystem,
         particle::{self, ParticleSpawnSystem},
-        trace_timer::TraceTimerSystem,
+        ranged_combat::RangedCombatSystem,
+        status::StatusSystem,
+        trace_timer::{TraceTimerSystem, DOWNLOAD_COST, LAYER_BONUS, SCRUB_BONUS, SCRUB_COMPUTE},
         visability::VisibilitySystem,
     },
+    DBG_SHOW_MAPGEN,
 };
 use rltk::{GameState, RandomNumberGenerator, Rltk};
 use specs::{prelude::*, rayon::iter::ParallelExtend};
@@ -27,43 +37,77 @@ pub struct State {
     pub ecs: World,
 }
 
+const MAPGEN_FRAME_MS: f32 = 100.0;
+
+/// Snapshots of the last generated map, replayed by `RunState::MapGeneration`
+#[derive(Default)]
+pub struct MapGenHistory {
+    pub snapshots: Vec<Map>,
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
     PreRun,
     AwaitingInput,
     PlayerTurn,
-    NPCTurn,
+    Ticking,
     ShowInventory,
+    ShowAbilities,
     Targeting {
         range: i32,
         item: Entity,
         radius: Option<i32>,
     },
+    AbilityTargeting {
+        range: i32,
+        ability: Ability,
+    },
+    ShootTargeting {
+        range: i32,
+    },
     MainMenu(MainMenuSelection),
     SaveGame,
     LoadGame,
     NextLayer,
+    PreviousLayer,
     RevealMap(i32),
+    ServiceTerminal,
+    MapGeneration {
+        frame: usize,
+        timer: f32,
+    },
     LevelUpMenu(i32),
     GameOver,
 }
 
 impl State {
     fn run_systems(&mut self) {
+        let mut initiative = InitiativeSystem;
+        initiative.run_now(&self.ecs);
+        let mut status = StatusSystem;
+        status.run_now(&self.ecs);
         let mut vis = VisibilitySystem;
         vis.run_now(&self.ecs);
+        let mut navigation = NavigationSystem;
+        navigation.run_now(&self.ecs);
         let mut enemy = EnemyAI;
         enemy.run_now(&self.ecs);
         let mut map_sys = MapSystem;
         map_sys.run_now(&self.ecs);
         let mut melee_sys = MeleeCombatSystem;
         melee_sys.run_now(&self.ecs);
+        let mut ranged_sys = RangedCombatSystem;
+        ranged_sys.run_now(&self.ecs);
         let mut damage_sys = DamageSystem;
         damage_sys.run_now(&self.ecs);
         let mut item_collection = ItemCollectionSystem;
         item_collection.run_now(&self.ecs);
         let mut item_consumption = ItemConsumptionSystem;
         item_consumption.run_now(&self.ecs);
+        let mut hacking = HackingSystem;
+        hacking.run_now(&self.ecs);
+        let mut noise = NoiseSystem;
+        noise.run_now(&self.ecs);
         let mut trace = TraceTimerSystem;
         trace.run_now(&self.ecs);
         let mut particles = ParticleSpawnSystem;
@@ -75,11 +119,17 @@ impl State {
     fn delete_dead(&mut self) {
         let mut dead = Vec::new();
         let mut items = Vec::new();
+        let mut loot = Vec::new();
         {
             let stats = self.ecs.read_storage::<Stats>();
             let entities = self.ecs.entities();
             let player = self.ecs.read_resource::<Player>();
             let names = self.ecs.read_storage::<Name>();
+            let drops = self.ecs.read_storage::<Drops>();
+            let xp_values = self.ecs.read_storage::<XpValue>();
+            let killed_by = self.ecs.read_storage::<KilledBy>();
+            let mut experience = self.ecs.write_storage::<Experience>();
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
             let inventory = self.ecs.read_storage::<HasInventory>();
             let mut in_inventory = self.ecs.write_storage::<InInventory>();
             let mut position = self.ecs.write_storage::<Position>();
@@ -102,6 +152,26 @@ impl State {
                         if let Some(victim_name) = victim_name {
                             log.entry(format!("{} is dead", &victim_name.name));
                         }
+                        // Only kills the player made themselves are worth anything
+                        let player_kill = killed_by
+                            .get(ent)
+                            .is_some_and(|k| k.killer == player.entity);
+                        if let (true, Some(value), Some(exp)) = (
+                            player_kill,
+                            xp_values.get(ent),
+                            experience.get_mut(player.entity),
+                        ) {
+                            log.entry(format!("You gain {} xp", value.xp));
+                            if exp.gain(value.xp) > 0 {
+                                log.entry(format!("You reach level {}", exp.level));
+                            }
+                        }
+                        if let Some(item) = drops.get(ent).and_then(|d| d.table.roll(&mut rng)) {
+                            if let Some(victim_name) = victim_name {
+                                log.entry(format!("{} drops {}", &victim_name.name, item));
+                            }
+                            loot.push((item.to_string(), *pos));
+                        }
                         dead.push(ent)
                     } else {
                         log.entry("You are dead".into());
@@ -120,67 +190,195 @@ impl State {
         self.ecs
             .delete_entities(&dead)
             .expect("Unable to delete dead");
+        for (item, pos) in loot {
+            raws::spawn_named(&mut self.ecs, &item, pos.x, pos.y);
+        }
         self.ecs.maintain();
     }
 
-    fn next_layer(&mut self) {
-        let delete = self.delete_on_level_change();
-
+    /// Level ups the player earned but hasn't spent yet
+    fn pending_levels(&self) -> i32 {
+        let player = self.ecs.fetch::<Player>().entity;
         self.ecs
-            .delete_entities(&delete)
-            .expect("Couldnt delete entities on level change");
-        {
-            let (dim_x, dim_y, layer) = {
-                let old_map = self.ecs.read_resource::<Map>();
-                (old_map.dim_x, old_map.dim_y, old_map.layer + 1)
+            .read_storage::<Experience>()
+            .get(player)
+            .map_or(0, |exp| exp.pending)
+    }
+
+    /// Whatever on a tile can be shot or hacked
+    fn target_at(&self, x: i32, y: i32) -> Option<Entity> {
+        let map = self.ecs.fetch::<Map>();
+        let stats = self.ecs.read_storage::<Stats>();
+        map.tile_content[map.coords_to_idx(x, y)]
+            .iter()
+            .copied()
+            .find(|&e| stats.contains(e))
+    }
+
+    /// Brings in the hunters the trace timer sent after the player
+    fn dispatch_hunters(&mut self) {
+        let (player, range, pending) = {
+            let player = self.ecs.read_resource::<Player>();
+            let viewsheds = self.ecs.read_storage::<Viewshed>();
+            let mut trace = self.ecs.write_storage::<TraceTimer>();
+            let trace = trace
+                .get_mut(player.entity)
+                .expect("Player has no trace component");
+            let range = viewsheds.get(player.entity).map_or(8, |v| v.range);
+            (
+                player.position.as_point(),
+                range,
+                std::mem::take(&mut trace.pending_hunters),
+            )
+        };
+        for _ in 0..pending {
+            let spot = {
+                let map = self.ecs.fetch::<Map>();
+                let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+                spawner::edge_of_vision(&map, player, range, &mut rng)
             };
-            self.generate_map(dim_x, dim_y, layer)
+            let Some(spot) = spot else { break };
+            if raws::spawn_named(&mut self.ecs, "Corp Hunter", spot.x, spot.y).is_some() {
+                let mut map = self.ecs.write_resource::<Map>();
+                let idx = map.coords_to_idx(spot.x, spot.y);
+                map.passable[idx] = false;
+            }
         }
+        if pending > 0 {
+            let mut log = self.ecs.write_resource::<GameLog>();
+            log.entry("You hear corporate hunters closing in".into());
+        }
+    }
 
+    /// Trades with the service terminal the player stands on
+    fn use_terminal(&mut self, action: TerminalAction) -> RunState {
+        let player = self.ecs.fetch::<Player>();
+        let mut trace = self.ecs.write_storage::<TraceTimer>();
+        let trace = trace
+            .get_mut(player.entity)
+            .expect("Player has no trace component");
+        let mut log = self.ecs.write_resource::<GameLog>();
+        match action {
+            TerminalAction::DownloadMap => {
+                trace.timer -= DOWNLOAD_COST;
+                log.entry(format!(
+                    "You download the layer map, trace -{}",
+                    DOWNLOAD_COST
+                ));
+                RunState::RevealMap(0)
+            }
+            TerminalAction::ScrubLogs => {
+                let mut stats = self.ecs.write_storage::<Stats>();
+                let stats = stats
+                    .get_mut(player.entity)
+                    .expect("Player has no stats component");
+                if stats.compute < SCRUB_COMPUTE {
+                    log.entry("Not enough compute to scrub the logs".into());
+                    return RunState::AwaitingInput;
+                }
+                stats.compute -= SCRUB_COMPUTE;
+                trace.timer += SCRUB_BONUS;
+                // Each terminal only has one set of logs to wipe
+                let mut map = self.ecs.write_resource::<Map>();
+                map[(player.position.x, player.position.y)] = Tile::Floor;
+                log.entry(format!(
+                    "You scrub the access logs, trace +{}, the terminal burns out",
+                    SCRUB_BONUS
+                ));
+                RunState::PlayerTurn
+            }
+        }
+    }
+
+    fn next_layer(&mut self) {
+        // Only a layer the corporation hasn't seen the player on buys time
+        if !self.change_layer(1) {
+            let mut log = self.ecs.write_resource::<GameLog>();
+            log.entry("You descend to the next network layer".into());
+            return;
+        }
+
+        // The pursuit doesn't follow the player down, whatever the deficit
         self.ecs.exec(
             |(p, mut t): (ReadExpect<Player>, WriteStorage<TraceTimer>)| {
-                t.get_mut(p.entity)
-                    .expect("Player has no trace component")
-                    .timer += 200
+                let trace = t.get_mut(p.entity).expect("Player has no trace component");
+                trace.timer = trace.timer.max(0) + LAYER_BONUS;
+                trace.alert = 0;
+                trace.pending_hunters = 0;
             },
         );
 
         let mut log = self.ecs.write_resource::<GameLog>();
-        log.entry("You descend to the next network layer".into())
+        log.entry(format!(
+            "You descend to the next network layer, trace +{}",
+            LAYER_BONUS
+        ))
     }
 
-    fn delete_on_level_change(&mut self) -> Vec<Entity> {
-        let entities = self.ecs.entities();
-        let player = self.ecs.fetch::<Player>();
-        let in_inventory = self.ecs.read_storage::<InInventory>();
-        entities
-            .par_join()
-            .filter(|&e| {
-                let in_player_inventory = if let Some(i) = in_inventory.get(e) {
-                    i.owner == player.entity
-                } else {
-                    false
-                };
-                !(e == player.entity || in_player_inventory)
-            })
-            .collect::<Vec<_>>()
+    fn previous_layer(&mut self) {
+        self.change_layer(-1);
+
+        let mut log = self.ecs.write_resource::<GameLog>();
+        log.entry("You ascend to the previous network layer".into())
     }
 
-    pub fn generate_map(&mut self, dim_x: i32, dim_y: i32, layer: i32) {
-        let mut builder = random_map_builder(dim_x, dim_y, layer);
-        let player_spawn = {
-            let player_spawn = {
-                let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
-                builder.build(&mut *rng);
-                builder.get_player_spawn(&mut *rng)
-            };
-            builder.spawn(&mut self.ecs);
-            player_spawn
+    /// Leaves the current layer as it is and either restores the target layer
+    /// or generates it if the player has never been there, returns whether it was generated
+    fn change_layer(&mut self, delta: i32) -> bool {
+        let (dim_x, dim_y, layer) = {
+            let old_map = self.ecs.read_resource::<Map>();
+            (old_map.dim_x, old_map.dim_y, old_map.layer + delta)
         };
+        dungeon::freeze_layer(&mut self.ecs);
+
+        if dungeon::thaw_layer(&mut self.ecs, layer) {
+            self.ecs.insert(MapGenHistory::default());
+            // Arrive at the terminal leading back to where the player came from
+            let terminal = if delta > 0 {
+                Tile::TerminalUp
+            } else {
+                Tile::TerminalDown
+            };
+            let (x, y) = self
+                .ecs
+                .fetch::<Map>()
+                .find_tile(terminal)
+                .expect("Visited layer has no terminal to arrive at");
+            self.place_player(Position { x, y });
+            false
+        } else {
+            self.generate_map(dim_x, dim_y, layer);
+            true
+        }
+    }
+
+    pub fn generate_map(&mut self, dim_x: i32, dim_y: i32, layer: i32) {
+        let seed = self.ecs.fetch::<RunSeed>().0;
+        let mut rng = layer_rng(seed, layer);
+        let mut builder = random_map_builder(dim_x, dim_y, layer, &mut rng);
+        builder.build(&mut rng, &self.ecs.fetch::<RawMaster>());
+        builder.spawn(&mut self.ecs);
+        let player_spawn = builder.get_player_spawn();
+        // A fresh run can land the player where the flow maps were last built
+        self.ecs.insert(Navigation::default());
+
+        let mut snapshots = Vec::new();
+        if DBG_SHOW_MAPGEN {
+            snapshots = builder.get_snapshots().to_vec();
+            for snapshot in snapshots.iter_mut() {
+                snapshot.revealed.fill(true);
+                snapshot.visible.fill(true);
+            }
+        }
+        self.ecs.insert(MapGenHistory { snapshots });
 
         let map_new = builder.get_map();
         *self.ecs.entry::<Map>().or_insert(map_new) = map_new.clone();
 
+        self.place_player(player_spawn);
+    }
+
+    fn place_player(&mut self, player_spawn: Position) {
         let mut player = self.ecs.fetch_mut::<Player>();
         player.position = player_spawn;
         let mut positions = self.ecs.write_storage::<Position>();
@@ -200,7 +398,7 @@ impl GameState for State {
         let mut new_run_state = { *self.ecs.fetch::<RunState>() };
 
         match new_run_state {
-            RunState::MainMenu(_) => {}
+            RunState::MainMenu(_) | RunState::MapGeneration { .. } => {}
             _ => {
                 camera::render(&self.ecs, ctx);
                 particle::particle_lifecycle(&mut self.ecs, ctx);
@@ -212,15 +410,29 @@ impl GameState for State {
                 self.run_systems();
                 RunState::AwaitingInput
             }
-            RunState::AwaitingInput => player_input(&mut self.ecs, ctx),
+            RunState::AwaitingInput => {
+                if self.pending_levels() > 0 {
+                    RunState::LevelUpMenu(LEVEL_UP_POINTS)
+                } else {
+                    player_input(&mut self.ecs, ctx)
+                }
+            }
             RunState::PlayerTurn => {
                 self.run_systems();
-                RunState::NPCTurn
+                self.dispatch_hunters();
+                RunState::Ticking
             }
-            RunState::NPCTurn => {
+            RunState::Ticking => loop {
                 self.run_systems();
-                RunState::AwaitingInput
-            }
+                self.delete_dead();
+                if *self.ecs.fetch::<RunState>() == RunState::GameOver {
+                    break RunState::GameOver;
+                }
+                let player = self.ecs.fetch::<Player>().entity;
+                if self.ecs.read_storage::<MyTurn>().contains(player) {
+                    break RunState::AwaitingInput;
+                }
+            },
             RunState::ShowInventory => match show_inventory(&mut self.ecs, ctx) {
                 crate::gui::ItemMenuResult::Cancel => RunState::AwaitingInput,
                 crate::gui::ItemMenuResult::NoResponse => RunState::ShowInventory,
@@ -249,18 +461,16 @@ impl GameState for State {
                                     radius: None,
                                 };
                             }
-                            Effect::DamageAOE {
-                                range,
-                                damage: _,
-                                radius,
-                            } => {
+                            Effect::DamageAOE { range, radius, .. } => {
                                 new_state = RunState::Targeting {
                                     range: *range,
                                     item: e,
                                     radius: Some(*radius),
                                 };
                             }
-                            Effect::Recharge(_) => {
+                            Effect::Recharge(_)
+                            | Effect::ExtendTrace(_)
+                            | Effect::SpoofTrace(_) => {
                                 intent
                                     .insert(
                                         self.ecs.fetch::<Player>().entity,
@@ -311,17 +521,85 @@ impl GameState for State {
                         .expect("Unable to insert intent");
                     RunState::PlayerTurn
                 }
-                TargetingResult::Entity(_) => todo!(),
                 TargetingResult::NoResponse => RunState::Targeting {
                     range,
                     item,
                     radius,
                 },
             },
+            RunState::ShowAbilities => match show_abilities(&mut self.ecs, ctx) {
+                AbilityMenuResult::Cancel => RunState::AwaitingInput,
+                AbilityMenuResult::NoResponse => RunState::ShowAbilities,
+                AbilityMenuResult::Selected(ability) => match ability.range() {
+                    Some(range) => RunState::AbilityTargeting { range, ability },
+                    None => {
+                        let mut intent = self.ecs.write_storage::<WantsToHack>();
+                        intent
+                            .insert(
+                                self.ecs.fetch::<Player>().entity,
+                                WantsToHack {
+                                    ability,
+                                    target: Target::Itself,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        RunState::PlayerTurn
+                    }
+                },
+            },
+            RunState::AbilityTargeting { range, ability } => {
+                match show_targeting(&mut self.ecs, ctx, range, None) {
+                    TargetingResult::Cancel => RunState::AwaitingInput,
+                    TargetingResult::Tile(x, y) if self.target_at(x, y).is_none() => {
+                        self.ecs
+                            .write_resource::<GameLog>()
+                            .entry("There is nothing to hack there".into());
+                        RunState::AwaitingInput
+                    }
+                    TargetingResult::Tile(x, y) => {
+                        let mut intent = self.ecs.write_storage::<WantsToHack>();
+                        intent
+                            .insert(
+                                self.ecs.fetch::<Player>().entity,
+                                WantsToHack {
+                                    ability,
+                                    target: Target::Tile(x, y),
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        RunState::PlayerTurn
+                    }
+                    TargetingResult::NoResponse => RunState::AbilityTargeting { range, ability },
+                }
+            }
+            RunState::ShootTargeting { range } => {
+                match show_targeting(&mut self.ecs, ctx, range, None) {
+                    TargetingResult::Cancel => RunState::AwaitingInput,
+                    TargetingResult::Tile(x, y) => match self.target_at(x, y) {
+                        Some(target) => {
+                            self.ecs
+                                .write_storage::<RangedAttack>()
+                                .insert(self.ecs.fetch::<Player>().entity, RangedAttack { target })
+                                .expect("Unable to insert intent");
+                            RunState::PlayerTurn
+                        }
+                        None => {
+                            self.ecs
+                                .write_resource::<GameLog>()
+                                .entry("There is nothing to shoot at there".into());
+                            RunState::AwaitingInput
+                        }
+                    },
+                    TargetingResult::NoResponse => RunState::ShootTargeting { range },
+                }
+            }
             RunState::MainMenu(_) => match show_main_menu(&mut self.ecs, ctx) {
                 crate::gui::MainMenuResult::Selected(sel) => RunState::MainMenu(sel),
                 crate::gui::MainMenuResult::Confirmed(selection) => match selection {
-                    MainMenuSelection::NewGame => RunState::PreRun,
+                    MainMenuSelection::NewGame => RunState::MapGeneration {
+                        frame: 0,
+                        timer: 0.0,
+                    },
                     MainMenuSelection::SaveGame => RunState::SaveGame,
                     MainMenuSelection::LoadGame => {
                         load_game(&mut self.ecs);
@@ -337,7 +615,34 @@ impl GameState for State {
             RunState::LoadGame => unreachable!(),
             RunState::NextLayer => {
                 self.next_layer();
-                RunState::PreRun
+                RunState::MapGeneration {
+                    frame: 0,
+                    timer: 0.0,
+                }
+            }
+            RunState::PreviousLayer => {
+                self.previous_layer();
+                RunState::MapGeneration {
+                    frame: 0,
+                    timer: 0.0,
+                }
+            }
+            RunState::MapGeneration { frame, timer } => {
+                let history = self.ecs.fetch::<MapGenHistory>();
+                if frame >= history.snapshots.len() {
+                    RunState::PreRun
+                } else {
+                    camera::render_snapshot(&self.ecs, &history.snapshots[frame], ctx);
+                    let timer = timer + ctx.frame_time_ms;
+                    if timer > MAPGEN_FRAME_MS {
+                        RunState::MapGeneration {
+                            frame: frame + 1,
+                            timer: 0.0,
+                        }
+                    } else {
+                        RunState::MapGeneration { frame, timer }
+                    }
+                }
             }
             RunState::RevealMap(mut y) => {
                 let mut map = self.ecs.write_resource::<Map>();
@@ -352,7 +657,12 @@ impl GameState for State {
                     RunState::RevealMap(y)
                 }
             }
-            RunState::LevelUpMenu(amount) => match show_levelup(ctx) {
+            RunState::ServiceTerminal => match show_terminal(ctx) {
+                TerminalMenuResult::Cancel => RunState::AwaitingInput,
+                TerminalMenuResult::NoResponse => RunState::ServiceTerminal,
+                TerminalMenuResult::Selected(action) => self.use_terminal(action),
+            },
+            RunState::LevelUpMenu(amount) => match show_levelup(ctx, self.pending_levels() == 0) {
                 crate::gui::LevelUpMenuResult::Cancel => RunState::AwaitingInput,
                 crate::gui::LevelUpMenuResult::NoResponse => RunState::LevelUpMenu(amount),
                 crate::gui::LevelUpMenuResult::Selected(stat) => {
@@ -366,15 +676,27 @@ impl GameState for State {
                         "HLT" => player_stats.base_hp += amount,
                         _ => unreachable!(),
                     };
+                    // Menus opened for pending levels keep coming back until every level is spent
+                    if let Some(exp) = self
+                        .ecs
+                        .write_storage::<Experience>()
+                        .get_mut(player.entity)
+                    {
+                        exp.pending = i32::max(exp.pending - 1, 0);
+                    }
                     RunState::AwaitingInput
                 }
             },
             RunState::GameOver => {
                 {
-                    let player = self.ecs.fetch::<Player>().entity;
-                    self.ecs
-                        .delete_entity(player)
-                        .expect("Failed to delete dead player");
+                    let seed = self
+                        .ecs
+                        .write_resource::<RandomNumberGenerator>()
+                        .rand::<u64>();
+                    self.ecs.insert(RunSeed(seed));
+                    // Everything left on the visited layers belongs to the previous run
+                    self.ecs.insert(MasterDungeon::default());
+                    self.ecs.delete_all();
                     let new_player = spawner::player(&mut self.ecs);
                     let mut player = self.ecs.fetch_mut::<Player>();
                     *player = new_player;
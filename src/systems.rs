@@ -1,8 +1,14 @@
 pub mod ai;
 pub mod bonus_system;
+pub mod hacking;
+pub mod initiative;
 pub mod inventory_system;
 pub mod map_system;
 pub mod melee_combat;
+pub mod navigation;
+pub mod noise;
 pub mod particle;
+pub mod ranged_combat;
+pub mod status;
 pub mod trace_timer;
 pub mod visability;
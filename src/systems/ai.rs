@@ -1,10 +1,52 @@
 use crate::{
-    components::{Enemy, MeleeAttack, Name, Position, Viewshed},
+    components::{
+        AiState, Enemy, Equipped, Faction, HasInventory, Hunter, Item, MeleeAttack, MyTurn, Pack,
+        Position, RangedAttack, RangedWeapon, Reaction, Stats, Viewshed, WantsToPickUp,
+    },
     map::Map,
     player::Player,
     state::RunState,
+    systems::{
+        navigation::Navigation,
+        ranged_combat::{has_line_of_fire, ranged_weapon_of},
+    },
 };
+use rltk::{BaseMap, Point, RandomNumberGenerator};
 use specs::{prelude::*, rayon::iter::IntoParallelIterator};
+use std::collections::{HashMap, HashSet};
+
+/// Shooters back off when their target gets closer than this
+const KEEP_DISTANCE: f32 = 2.5;
+/// Turns spent looking for a hostile that got out of sight
+pub const SEARCH_TURNS: i32 = 10;
+/// Mobs run once they are down to this fraction of their health
+const FLEE_HEALTH: f32 = 0.25;
+/// How far a wandering mob strays in one go
+const WANDER_RANGE: i32 = 8;
+/// Scavengers go for items at most this far away
+const SCAVENGE_RANGE: f32 = 12.0;
+
+/// Living members and health of a pack on this layer
+#[derive(Default)]
+struct PackStrength {
+    alive: i32,
+    hp: i32,
+    base_hp: i32,
+}
+
+impl PackStrength {
+    /// Down to less than half its members or half its health
+    fn weakened(&self, size: i32) -> bool {
+        self.alive * 2 < size || self.hp * 2 < self.base_hp
+    }
+}
+
+enum Action {
+    Move(Point),
+    Melee(Entity),
+    Ranged(Entity),
+    PickUp(Entity),
+}
 
 pub struct EnemyAI;
 
@@ -15,11 +57,24 @@ impl<'a> System<'a> for EnemyAI {
         WriteStorage<'a, Viewshed>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Enemy>,
-        ReadStorage<'a, Name>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Stats>,
         WriteExpect<'a, Map>,
         WriteStorage<'a, MeleeAttack>,
         ReadExpect<'a, RunState>,
+        ReadStorage<'a, MyTurn>,
+        ReadStorage<'a, RangedWeapon>,
+        WriteStorage<'a, RangedAttack>,
+        WriteStorage<'a, AiState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadExpect<'a, Navigation>,
         ReadExpect<'a, Player>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, HasInventory>,
+        WriteStorage<'a, WantsToPickUp>,
+        ReadStorage<'a, Pack>,
+        ReadStorage<'a, Hunter>,
+        ReadStorage<'a, Equipped>,
     );
 
     fn run(
@@ -29,61 +84,452 @@ impl<'a> System<'a> for EnemyAI {
             mut viewshed,
             mut pos,
             enemy,
-            name,
+            factions,
+            stats,
             mut map,
             mut melee,
             run_state,
+            turns,
+            weapons,
+            mut ranged,
+            mut ai_states,
+            mut rng,
+            navigation,
             player,
+            items,
+            inventories,
+            mut pickup,
+            packs,
+            hunters,
+            equipped,
         ): Self::SystemData,
     ) {
-        if *run_state != RunState::NPCTurn {
+        if *run_state != RunState::Ticking {
             return;
         }
-        let want_to_melee = (&entities, &mut viewshed, &name, &enemy, &mut pos)
+        // Everyone alive who can be fought, kept up to date as mobs move around
+        let mut actors = (&entities, &pos, &factions, &stats)
+            .join()
+            .filter(|(_, _, _, stats)| stats.hp > 0)
+            .map(|(ent, pos, faction, _)| {
+                let armed = ranged_weapon_of(ent, &weapons, &equipped).is_some();
+                (ent, pos.as_point(), *faction, armed)
+            })
+            .collect::<Vec<_>>();
+
+        // Whatever one member of a pack sees, the whole pack knows about
+        let mut sightings = HashMap::new();
+        let mut strength = HashMap::<u64, PackStrength>::new();
+        for (ent, viewshed, faction, pos, pack, stat) in
+            (&entities, &viewshed, &factions, &pos, &packs, &stats).join()
+        {
+            let entry = strength.entry(pack.id).or_default();
+            if stat.hp > 0 {
+                entry.alive += 1;
+                entry.hp += stat.hp;
+            }
+            entry.base_hp += stat.base_hp;
+            if let Some((_, p, Reaction::Attack)) =
+                nearest_hostile(&actors, ent, *faction, viewshed, pos.as_point())
+            {
+                sightings.insert(pack.id, p);
+            }
+        }
+        // Free tiles around a target that some pack member is already heading for
+        let mut claimed = HashSet::new();
+
+        let mut attacks = Vec::new();
+        for (ent, viewshed, _, faction, pos, _, state, stat) in (
+            &entities,
+            &mut viewshed,
+            &enemy,
+            &factions,
+            &mut pos,
+            &turns,
+            &mut ai_states,
+            &stats,
+        )
             .join()
-            .filter_map(|(ent, viewshed, _name, _, pos)| {
-                let distance = rltk::DistanceAlg::Pythagoras
-                    .distance2d(pos.as_point(), player.position.as_point());
-                if distance < 1.5 {
-                    return Some(ent);
+        {
+            let here = pos.as_point();
+            let distance = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(here, p);
+            let nearest = nearest_hostile(&actors, ent, *faction, viewshed, here);
+            let pack = packs.get(ent);
+            let shared = pack.and_then(|pack| sightings.get(&pack.id)).copied();
+            let pack_weakened = pack.is_some_and(|pack| {
+                strength
+                    .get(&pack.id)
+                    .is_some_and(|s| s.weakened(pack.size))
+            });
+
+            let hurt = (stat.hp as f32) <= stat.base_hp as f32 * FLEE_HEALTH || pack_weakened;
+            *state = think(*state, here, nearest.map(|(_, p, r)| (p, r)), hurt);
+            if let (None, Some(p)) = (nearest, shared) {
+                *state = if hurt {
+                    AiState::Flee
+                } else {
+                    think(AiState::Chase { x: p.x, y: p.y }, here, None, false)
+                };
+            }
+            // The trace leads hunters straight to the player
+            let tracking = nearest.is_none() && hunters.contains(ent) && !hurt;
+            if tracking {
+                let target = player.position;
+                *state = AiState::Chase {
+                    x: target.x,
+                    y: target.y,
+                };
+            }
+            let scavenging = *state == AiState::Idle
+                && *faction == Faction::Scavengers
+                && inventories.contains(ent)
+                && navigation
+                    .item_distance(&map, here)
+                    .is_some_and(|d| d <= SCAVENGE_RANGE);
+            if *state == AiState::Idle && !scavenging && rng.roll_dice(1, 4) == 1 {
+                if let Some(spot) = wander_destination(&map, &mut rng, here, &navigation.to_player)
+                {
+                    *state = AiState::Wander {
+                        x: spot.x,
+                        y: spot.y,
+                    };
                 }
-                if viewshed.visible_tiles.contains(&player.position.as_point()) {
-                    let end_positions = [
-                        map.coords_to_idx(player.position.x + 1, player.position.y),
-                        map.coords_to_idx(player.position.x - 1, player.position.y),
-                        map.coords_to_idx(player.position.x, player.position.y + 1),
-                        map.coords_to_idx(player.position.x, player.position.y - 1),
-                        map.coords_to_idx(player.position.x + 1, player.position.y + 1),
-                        map.coords_to_idx(player.position.x - 1, player.position.y + 1),
-                        map.coords_to_idx(player.position.x + 1, player.position.y - 1),
-                        map.coords_to_idx(player.position.x - 1, player.position.y - 1),
-                    ];
-                    let path = end_positions
-                        .into_par_iter()
-                        .map(|end_pos| {
-                            rltk::a_star_search(map.coords_to_idx(pos.x, pos.y), end_pos, &*map)
-                        })
-                        .find_any(|path| path.success && path.steps.len() > 1);
-
-                    if let Some(path) = path {
-                        pos.x = path.steps[1] as i32 % map.dim_x;
-                        pos.y = path.steps[1] as i32 / map.dim_x;
-                        map.passable[path.steps[1]] = false;
-                        viewshed.dirty = true;
+            }
+
+            let action = match (*state, nearest) {
+                (AiState::Chase { .. }, Some((target, target_pos, _))) => {
+                    let flow = (target == player.entity).then_some(&*navigation.to_player);
+                    let flank =
+                        pack.is_some() && weapons.get(ent).is_none() && distance(target_pos) >= 1.5;
+                    let step = flank
+                        .then(|| surround_spot(&map, here, target_pos, &claimed))
+                        .flatten()
+                        .and_then(|spot| Some((spot, step_closer(&map, here, spot)?)));
+                    if let Some((spot, _)) = step {
+                        claimed.insert(spot);
                     }
+                    step.map(|(_, step)| Action::Move(step))
+                        .or_else(|| attack(&map, here, target, target_pos, weapons.get(ent), flow))
                 }
-                None
-            })
-            .collect::<Vec<_>>();
-        for entity in want_to_melee {
-            melee
-                .insert(
-                    entity,
-                    MeleeAttack {
-                        target: player.entity,
-                    },
-                )
-                .expect("Failed to insert melee");
+                (AiState::Chase { .. }, None) if tracking => {
+                    Navigation::downhill(&navigation.to_player, &map, here).map(Action::Move)
+                }
+                // Cornered mobs fight back
+                (AiState::Flee, Some((target, target_pos, _))) => {
+                    let step = if target == player.entity {
+                        Navigation::downhill(&navigation.from_player, &map, here)
+                    } else {
+                        retreat_step(&map, here, target_pos)
+                    };
+                    step.map(Action::Move)
+                        .or_else(|| (distance(target_pos) < 1.5).then_some(Action::Melee(target)))
+                }
+                // Packs fall back together, even the members that can't see the threat
+                (AiState::Flee, None) => shared
+                    .and_then(|p| retreat_step(&map, here, p))
+                    .map(Action::Move),
+                (AiState::Idle, _) if scavenging => {
+                    let idx = map.coords_to_idx(here.x, here.y);
+                    match map.tile_content[idx].iter().find(|&&e| items.contains(e)) {
+                        Some(&item) => Some(Action::PickUp(item)),
+                        None => {
+                            Navigation::downhill(&navigation.to_items, &map, here).map(Action::Move)
+                        }
+                    }
+                }
+                (AiState::Search { x, y, .. }, _) | (AiState::Wander { x, y }, _) => {
+                    let step = step_closer(&map, here, Point::new(x, y));
+                    if step.is_none() {
+                        *state = AiState::Idle;
+                    }
+                    step.map(Action::Move)
+                }
+                _ => None,
+            };
+            match action {
+                Some(Action::Move(step)) => {
+                    pos.x = step.x;
+                    pos.y = step.y;
+                    let idx = map.coords_to_idx(step.x, step.y);
+                    map.passable[idx] = false;
+                    viewshed.dirty = true;
+                    if let Some(actor) = actors.iter_mut().find(|(other, _, _, _)| *other == ent) {
+                        actor.1 = step;
+                    }
+                }
+                Some(attack) => attacks.push((ent, attack)),
+                None => {}
+            }
+        }
+        for (entity, attack) in attacks {
+            match attack {
+                Action::Melee(target) => {
+                    melee
+                        .insert(entity, MeleeAttack { target })
+                        .expect("Failed to insert melee");
+                }
+                Action::Ranged(target) => {
+                    ranged
+                        .insert(entity, RangedAttack { target })
+                        .expect("Failed to insert ranged attack");
+                }
+                Action::PickUp(item) => {
+                    pickup
+                        .insert(
+                            entity,
+                            WantsToPickUp {
+                                collector: entity,
+                                item,
+                            },
+                        )
+                        .expect("Failed to insert pickup");
+                }
+                Action::Move(_) => unreachable!(),
+            }
+        }
+    }
+}
+
+/// The closest visible entity this faction doesn't ignore
+fn nearest_hostile(
+    actors: &[(Entity, Point, Faction, bool)],
+    ent: Entity,
+    faction: Faction,
+    viewshed: &Viewshed,
+    here: Point,
+) -> Option<(Entity, Point, Reaction)> {
+    let distance = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(here, p);
+    actors
+        .iter()
+        .filter(|(other, p, _, _)| *other != ent && viewshed.visible_tiles.contains(p))
+        .filter_map(|&(other, p, other_faction, armed)| {
+            match faction.reaction(other_faction, armed) {
+                Reaction::Ignore => None,
+                reaction => Some((other, p, reaction)),
+            }
+        })
+        .min_by(|a, b| distance(a.1).total_cmp(&distance(b.1)))
+}
+
+/// The free tile next to the target closest to us that no pack member has claimed yet
+fn surround_spot(map: &Map, from: Point, target: Point, claimed: &HashSet<Point>) -> Option<Point> {
+    let distance = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(from, p);
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| Point::new(target.x + dx, target.y + dy)))
+        .filter(|&p| p != target && !claimed.contains(&p))
+        .filter(|p| p.x > 0 && p.y > 0 && p.x < map.dim_x - 1 && p.y < map.dim_y - 1)
+        .filter(|p| map.passable[map.coords_to_idx(p.x, p.y)])
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+}
+
+/// Picks the next state from what the mob sees, `seen` is the nearest hostile worth reacting to
+fn think(state: AiState, here: Point, seen: Option<(Point, Reaction)>, hurt: bool) -> AiState {
+    let arrived = |x, y| here == Point::new(x, y);
+    match (seen, state) {
+        (Some((_, Reaction::Flee)), _) => AiState::Flee,
+        (Some(_), _) if hurt => AiState::Flee,
+        (Some((p, _)), _) => AiState::Chase { x: p.x, y: p.y },
+        (None, AiState::Chase { x, y }) => AiState::Search {
+            x,
+            y,
+            turns: SEARCH_TURNS,
+        },
+        (None, AiState::Search { x, y, turns }) if turns <= 1 || arrived(x, y) => AiState::Idle,
+        (None, AiState::Search { x, y, turns }) => AiState::Search {
+            x,
+            y,
+            turns: turns - 1,
+        },
+        (None, AiState::Wander { x, y }) if arrived(x, y) => AiState::Idle,
+        (None, AiState::Flee) => AiState::Idle,
+        (None, state) => state,
+    }
+}
+
+/// A random free tile not too far away that `reachable` has a way to
+fn wander_destination(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    from: Point,
+    reachable: &[f32],
+) -> Option<Point> {
+    (0..10)
+        .map(|_| {
+            Point::new(
+                from.x + rng.range(-WANDER_RANGE, WANDER_RANGE + 1),
+                from.y + rng.range(-WANDER_RANGE, WANDER_RANGE + 1),
+            )
+        })
+        .filter(|p| p.x > 0 && p.y > 0 && p.x < map.dim_x - 1 && p.y < map.dim_y - 1)
+        .find(|p| {
+            let idx = map.coords_to_idx(p.x, p.y);
+            *p != from && map.passable[idx] && reachable.get(idx).is_some_and(|&d| d < f32::MAX)
+        })
+}
+
+/// The free neighbouring tile closest to `to`, if it gets us any closer
+fn step_closer(map: &Map, from: Point, to: Point) -> Option<Point> {
+    let distance = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(p, to);
+    map.get_available_exits(map.coords_to_idx(from.x, from.y))
+        .into_iter()
+        .map(|(idx, _)| {
+            let (x, y) = map.idx_to_coords(idx);
+            Point::new(x, y)
+        })
+        .filter(|&p| distance(p) < distance(from))
+        .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+}
+
+/// Shoots, hits or closes in on a hostile target, following `flow` towards it when there is one
+fn attack(
+    map: &Map,
+    from: Point,
+    target: Entity,
+    to: Point,
+    weapon: Option<&RangedWeapon>,
+    flow: Option<&[f32]>,
+) -> Option<Action> {
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(from, to);
+    if let Some(weapon) = weapon {
+        if distance < KEEP_DISTANCE {
+            if let Some(step) = retreat_step(map, from, to) {
+                return Some(Action::Move(step));
+            }
+        }
+        if distance >= 1.5 && distance <= weapon.range as f32 && has_line_of_fire(map, from, to) {
+            return Some(Action::Ranged(target));
+        }
+    }
+    if distance < 1.5 {
+        return Some(Action::Melee(target));
+    }
+    match flow {
+        Some(flow) => Navigation::downhill(flow, map, from),
+        None => approach_step(map, from, to),
+    }
+    .map(Action::Move)
+}
+
+/// The first step of a path to any tile next to `to`
+fn approach_step(map: &Map, from: Point, to: Point) -> Option<Point> {
+    let end_positions = [
+        map.coords_to_idx(to.x + 1, to.y),
+        map.coords_to_idx(to.x - 1, to.y),
+        map.coords_to_idx(to.x, to.y + 1),
+        map.coords_to_idx(to.x, to.y - 1),
+        map.coords_to_idx(to.x + 1, to.y + 1),
+        map.coords_to_idx(to.x - 1, to.y + 1),
+        map.coords_to_idx(to.x + 1, to.y - 1),
+        map.coords_to_idx(to.x - 1, to.y - 1),
+    ];
+    end_positions
+        .into_par_iter()
+        .map(|end_pos| rltk::a_star_search(map.coords_to_idx(from.x, from.y), end_pos, map))
+        .find_any(|path| path.success && path.steps.len() > 1)
+        .map(|path| {
+            let (x, y) = map.idx_to_coords(path.steps[1]);
+            Point::new(x, y)
+        })
+}
+
+/// The free neighbouring tile farthest from the threat, if it is farther than where we stand
+fn retreat_step(map: &Map, from: Point, threat: Point) -> Option<Point> {
+    let distance = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(p, threat);
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| Point::new(from.x + dx, from.y + dy)))
+        .filter(|p| p.x > 0 && p.y > 0 && p.x < map.dim_x - 1 && p.y < map.dim_y - 1)
+        .filter(|p| map.passable[map.coords_to_idx(p.x, p.y)])
+        .filter(|&p| distance(p) > distance(from))
+        .max_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+}
+
+#[test]
+fn factions_pick_their_fights() {
+    use Faction::*;
+
+    for faction in [Player, Corporate, Scavengers, Feral] {
+        assert_eq!(faction.reaction(faction, false), Reaction::Ignore);
+        assert_eq!(
+            Corporate.reaction(faction, true) == Reaction::Attack,
+            faction != Corporate
+        );
+    }
+    assert_eq!(Feral.reaction(Player, false), Reaction::Attack);
+    // Animals know better than to charge a gun
+    assert_eq!(Feral.reaction(Player, true), Reaction::Flee);
+    assert_eq!(Scavengers.reaction(Feral, false), Reaction::Attack);
+    assert_eq!(Feral.reaction(Scavengers, false), Reaction::Flee);
+    assert_eq!(Scavengers.reaction(Corporate, false), Reaction::Flee);
+}
+
+#[test]
+fn losing_sight_turns_a_chase_into_a_search() {
+    let here = Point::new(5, 5);
+    let seen = Some((Point::new(8, 5), Reaction::Attack));
+    let state = think(AiState::Idle, here, seen, false);
+    assert_eq!(state, AiState::Chase { x: 8, y: 5 });
+    assert_eq!(think(state, here, seen, true), AiState::Flee);
+
+    let mut state = think(state, here, None, false);
+    assert_eq!(
+        state,
+        AiState::Search {
+            x: 8,
+            y: 5,
+            turns: SEARCH_TURNS
+        }
+    );
+    for _ in 0..SEARCH_TURNS {
+        assert!(matches!(state, AiState::Search { .. }));
+        state = think(state, here, None, false);
+    }
+    assert_eq!(state, AiState::Idle);
+    // Reaching the spot ends the search early
+    let search = AiState::Search {
+        x: 5,
+        y: 5,
+        turns: 3,
+    };
+    assert_eq!(think(search, here, None, false), AiState::Idle);
+}
+
+#[test]
+fn packs_spread_around_their_prey() {
+    use crate::map::Tile;
+
+    let mut map = Map::new(12, 12, 0);
+    for y in 1..11 {
+        for x in 1..11 {
+            map[(x, y)] = Tile::Floor;
         }
     }
+    map.reset_runtime_state();
+    let target = Point::new(6, 6);
+    let mut claimed = HashSet::new();
+    assert_eq!(
+        step_closer(&map, Point::new(1, 6), target),
+        Some(Point::new(2, 6))
+    );
+    for _ in 0..8 {
+        let spot = surround_spot(&map, Point::new(1, 6), target, &claimed).unwrap();
+        assert!(rltk::DistanceAlg::Chebyshev.distance2d(spot, target) == 1.0);
+        assert!(claimed.insert(spot));
+    }
+    assert_eq!(
+        surround_spot(&map, Point::new(1, 6), target, &claimed),
+        None
+    );
+
+    let strength = PackStrength {
+        alive: 2,
+        hp: 6,
+        base_hp: 15,
+    };
+    assert!(strength.weakened(3));
+    assert!(!PackStrength {
+        alive: 2,
+        hp: 8,
+        base_hp: 15
+    }
+    .weakened(3));
 }
@@ -0,0 +1,163 @@
+use crate::{
+    components::{
+        Ability, DamageType, Emp, InflictStatus, Name, Stats, StatusEffect, TakeDamage, Target,
+        TraceTimer, WantsToHack,
+    },
+    gui::GameLog,
+    map::Map,
+    player::Player,
+};
+use rltk::Point;
+use specs::prelude::*;
+
+use super::particle;
+
+const OVERLOAD_DAMAGE: i32 = 8;
+const OVERLOAD_STUN_TURNS: i32 = 1;
+const BLIND_TURNS: i32 = 5;
+const SCRAMBLE_TURNS: i32 = 4;
+const PING_RADIUS: i32 = 20;
+
+/// Runs the programs queued up with `WantsToHack`, paying for them with compute
+pub struct HackingSystem;
+
+impl<'a> System<'a> for HackingSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToHack>,
+        WriteStorage<'a, Stats>,
+        WriteStorage<'a, TakeDamage>,
+        WriteStorage<'a, InflictStatus>,
+        ReadStorage<'a, Emp>,
+        ReadStorage<'a, Name>,
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, particle::RequestQueue>,
+        ReadExpect<'a, Player>,
+        WriteStorage<'a, TraceTimer>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut wants_hack,
+            mut stats,
+            mut take_damage,
+            mut inflict,
+            emp,
+            names,
+            mut map,
+            mut log,
+            mut particle_request,
+            player,
+            mut trace,
+        ): Self::SystemData,
+    ) {
+        for (hacker, hack) in (&entities, &wants_hack).join() {
+            let Some(hacker_stats) = stats.get_mut(hacker) else {
+                continue;
+            };
+            if emp.contains(hacker) {
+                if hacker == player.entity {
+                    log.entry("Your compute is jammed by the EMP".into());
+                }
+                continue;
+            }
+            if hacker_stats.compute < hack.ability.cost() {
+                if hacker == player.entity {
+                    log.entry(format!("Not enough compute to run {}", hack.ability.name()));
+                }
+                continue;
+            }
+            hacker_stats.compute -= hack.ability.cost();
+            // Hacking the network is loud, the corporation follows the traffic
+            if let Some(trace) = trace.get_mut(hacker) {
+                trace.timer -= hack.ability.trace_cost();
+                log.entry(format!(
+                    "{} leaves a trail, trace -{}",
+                    hack.ability.name(),
+                    hack.ability.trace_cost()
+                ));
+            }
+
+            let targets = match hack.target {
+                Target::Tile(x, y) => {
+                    particle_request.request(
+                        x,
+                        y,
+                        rltk::RGB::named(rltk::CYAN),
+                        rltk::RGB::named(rltk::BLACK),
+                        rltk::to_cp437('¤'),
+                        200.0,
+                    );
+                    let idx = map.coords_to_idx(x, y);
+                    map.tile_content[idx]
+                        .iter()
+                        .copied()
+                        .filter(|&e| stats.contains(e))
+                        .collect::<Vec<_>>()
+                }
+                Target::Other(e) => vec![e],
+                Target::Itself => vec![hacker],
+            };
+
+            match hack.ability {
+                Ability::Overload => {
+                    for target in targets {
+                        TakeDamage::new_damage(
+                            &mut take_damage,
+                            target,
+                            OVERLOAD_DAMAGE,
+                            DamageType::Electric,
+                            Some(hacker),
+                        );
+                        InflictStatus::new_status(
+                            &mut inflict,
+                            target,
+                            StatusEffect::Stun(OVERLOAD_STUN_TURNS),
+                        );
+                        if let Some(name) = names.get(target) {
+                            log.entry(format!(
+                                "You overload {}, frying it for {} hp",
+                                name, OVERLOAD_DAMAGE
+                            ));
+                        }
+                    }
+                }
+                Ability::Blind => {
+                    for target in targets {
+                        InflictStatus::new_status(
+                            &mut inflict,
+                            target,
+                            StatusEffect::Blind(BLIND_TURNS),
+                        );
+                    }
+                }
+                Ability::Scramble => {
+                    for target in targets {
+                        InflictStatus::new_status(
+                            &mut inflict,
+                            target,
+                            StatusEffect::Confuse(SCRAMBLE_TURNS),
+                        );
+                    }
+                }
+                Ability::Ping => {
+                    let center = player.position.as_point();
+                    for idx in 0..map.size() {
+                        let (x, y) = map.idx_to_coords(idx);
+                        let distance =
+                            rltk::DistanceAlg::Pythagoras.distance2d(center, Point::new(x, y));
+                        if distance <= PING_RADIUS as f32 {
+                            map.revealed[idx] = true;
+                        }
+                    }
+                    log.entry("The ping echoes back the layout of the network".into());
+                }
+            }
+        }
+        wants_hack.clear();
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    components::{EquipBonus, Equipped, Initiative, MyTurn, Position},
+    state::RunState,
+};
+use specs::prelude::*;
+use std::collections::HashMap;
+
+/// Energy an actor spends to take a turn
+pub const ACTION_COST: i32 = 100;
+
+/// Hands out turns to whoever has gathered enough energy,
+/// everyone gains energy by their speed when nobody has
+pub struct InitiativeSystem;
+
+impl<'a> System<'a> for InitiativeSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Initiative>,
+        WriteStorage<'a, MyTurn>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, EquipBonus>,
+        ReadExpect<'a, RunState>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut initiative, mut turns, positions, equipped, bonus, run_state): Self::SystemData,
+    ) {
+        if *run_state != RunState::Ticking || (&initiative, &positions).join().next().is_none() {
+            return;
+        }
+        turns.clear();
+
+        let mut speed_bonus = HashMap::new();
+        for (equipped, bonus) in (&equipped, &bonus).join() {
+            if let EquipBonus::Speed(speed) = bonus {
+                *speed_bonus.entry(equipped.owner).or_insert(0) += speed;
+            }
+        }
+
+        loop {
+            let ready = (&entities, &initiative, &positions)
+                .join()
+                .filter(|(_, i, _)| i.energy >= ACTION_COST)
+                .map(|(e, _, _)| e)
+                .collect::<Vec<_>>();
+            if ready.is_empty() {
+                for (e, i, _) in (&entities, &mut initiative, &positions).join() {
+                    let speed = i.speed + speed_bonus.get(&e).copied().unwrap_or(0);
+                    // Nothing is allowed to stall the scheduler completely
+                    i.energy += i32::max(speed, 10);
+                }
+                continue;
+            }
+            for e in ready {
+                if let Some(i) = initiative.get_mut(e) {
+                    i.energy -= ACTION_COST;
+                }
+                turns.insert(e, MyTurn).expect("Unable to insert turn");
+            }
+            break;
+        }
+    }
+}
+
+#[test]
+fn faster_actors_get_more_turns() {
+    let mut ecs = World::new();
+    ecs.register::<Initiative>();
+    ecs.register::<MyTurn>();
+    ecs.register::<Position>();
+    ecs.register::<Equipped>();
+    ecs.register::<EquipBonus>();
+    ecs.insert(RunState::Ticking);
+
+    let mut actor = |speed| {
+        ecs.create_entity()
+            .with(Position { x: 0, y: 0 })
+            .with(Initiative { speed, energy: 0 })
+            .build()
+    };
+    let (player, snake, skel) = (actor(100), actor(200), actor(75));
+    let boots = ecs.create_entity().with(EquipBonus::Speed(100)).build();
+
+    let mut turns = HashMap::new();
+    let mut take_turns = |ecs: &mut World, player_turns| {
+        turns.clear();
+        let mut taken = 0;
+        while taken < player_turns {
+            InitiativeSystem.run_now(ecs);
+            for (e, _) in (&ecs.entities(), &ecs.read_storage::<MyTurn>()).join() {
+                *turns.entry(e).or_insert(0) += 1;
+            }
+            if ecs.read_storage::<MyTurn>().contains(player) {
+                taken += 1;
+            }
+        }
+        turns.clone()
+    };
+
+    // Counting stops on the player's turn, so whoever acts after it may be one short
+    let turns = take_turns(&mut ecs, 40);
+    assert!((79..=80).contains(&turns[&snake]));
+    assert!((29..=30).contains(&turns[&skel]));
+
+    ecs.write_storage::<Equipped>()
+        .insert(
+            boots,
+            Equipped {
+                owner: player,
+                slot: crate::components::Slot::Body,
+                item: boots,
+            },
+        )
+        .unwrap();
+    let turns = take_turns(&mut ecs, 40);
+    assert!((39..=41).contains(&turns[&snake]));
+}
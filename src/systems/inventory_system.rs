@@ -1,8 +1,8 @@
 use crate::{components::*, gui::GameLog, map::Map, player::Player};
-use rltk::Point;
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
-use super::particle;
+use super::{noise, particle};
 
 pub struct ItemCollectionSystem;
 
@@ -75,7 +75,12 @@ impl<'a> System<'a> for ItemConsumptionSystem {
         ReadStorage<'a, Slots>,
         WriteStorage<'a, InInventory>,
         WriteExpect<'a, particle::RequestQueue>,
+        WriteExpect<'a, noise::NoiseQueue>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Inflicts>,
+        WriteStorage<'a, InflictStatus>,
+        WriteStorage<'a, TraceTimer>,
+        WriteExpect<'a, RandomNumberGenerator>,
     );
 
     fn run(
@@ -96,7 +101,12 @@ impl<'a> System<'a> for ItemConsumptionSystem {
             slots,
             mut in_invenory,
             mut particle_request,
+            mut noises,
             positions,
+            inflicts,
+            mut inflict_status,
+            mut trace,
+            mut rng,
         ): Self::SystemData,
     ) {
         for (entity, wants, stats) in (&entities, &wants_use, &mut combat_stats).join() {
@@ -125,7 +135,11 @@ impl<'a> System<'a> for ItemConsumptionSystem {
                         }
                         entities.delete(wants.item).expect("Delete failed");
                     }
-                    Effect::DamageRanged { range: _, damage } => match wants.target {
+                    Effect::DamageRanged {
+                        range: _,
+                        damage,
+                        kind,
+                    } => match wants.target {
                         Target::Itself => todo!(),
                         Target::Other(_) => todo!(),
                         Target::Tile(x, y) => {
@@ -143,13 +157,29 @@ impl<'a> System<'a> for ItemConsumptionSystem {
                                             200.0,
                                         );
                                     }
-                                    TakeDamage::new_damage(&mut take_damage, *mob, *damage);
+                                    TakeDamage::new_damage(
+                                        &mut take_damage,
+                                        *mob,
+                                        *damage,
+                                        *kind,
+                                        Some(entity),
+                                    );
+                                    if let Some(inflicts) = inflicts.get(wants.item) {
+                                        if rng.roll_dice(1, 100) <= inflicts.chance {
+                                            InflictStatus::new_status(
+                                                &mut inflict_status,
+                                                *mob,
+                                                inflicts.status,
+                                            );
+                                        }
+                                    }
                                     if entity == player.entity {
                                         gamelog.entry(format!(
-                                            "You use the {}, damaging {} for {} hp.",
+                                            "You use the {}, dealing {} {} damage to {}.",
                                             names.get(wants.item).unwrap().name,
+                                            damage,
+                                            kind,
                                             names.get(*mob).unwrap(),
-                                            damage
                                         ));
                                     }
                                 }
@@ -161,10 +191,12 @@ impl<'a> System<'a> for ItemConsumptionSystem {
                         range: _,
                         damage,
                         radius,
+                        kind,
                     } => match wants.target {
                         Target::Itself => todo!(),
                         Target::Other(_) => todo!(),
                         Target::Tile(x, y) => {
+                            noises.make(x, y, noise::EXPLOSION_VOLUME);
                             let mut blast_tiles =
                                 rltk::field_of_view(Point::new(x, y), *radius, &*map);
                             blast_tiles.retain(|p| {
@@ -182,13 +214,29 @@ impl<'a> System<'a> for ItemConsumptionSystem {
 
                                 let idx = map.coords_to_idx(title_coords.x, title_coords.y);
                                 for mob in &map.tile_content[idx] {
-                                    TakeDamage::new_damage(&mut take_damage, *mob, *damage);
+                                    TakeDamage::new_damage(
+                                        &mut take_damage,
+                                        *mob,
+                                        *damage,
+                                        *kind,
+                                        Some(entity),
+                                    );
+                                    if let Some(inflicts) = inflicts.get(wants.item) {
+                                        if rng.roll_dice(1, 100) <= inflicts.chance {
+                                            InflictStatus::new_status(
+                                                &mut inflict_status,
+                                                *mob,
+                                                inflicts.status,
+                                            );
+                                        }
+                                    }
                                     if entity == player.entity {
                                         gamelog.entry(format!(
-                                            "You use the {}, damaging {} for {} hp.",
+                                            "You use the {}, dealing {} {} damage to {}.",
                                             names.get(wants.item).unwrap().name,
+                                            damage,
+                                            kind,
                                             names.get(*mob).unwrap(),
-                                            damage
                                         ));
                                     }
                                 }
@@ -208,16 +256,38 @@ impl<'a> System<'a> for ItemConsumptionSystem {
                                 200.0,
                             );
                         }
-                        stats.hp = i32::min(stats.base_compute, stats.compute + amount);
+                        stats.compute = i32::min(stats.base_compute, stats.compute + amount);
                         if entity == player.entity {
                             gamelog.entry(format!(
-                                "You use the {}, healing {} hp.",
+                                "You use the {}, restoring {} compute.",
                                 names.get(wants.item).unwrap(),
                                 amount
                             ));
                         }
                         entities.delete(wants.item).expect("Delete failed");
                     }
+                    Effect::ExtendTrace(turns) => {
+                        if let Some(trace) = trace.get_mut(entity) {
+                            trace.timer += turns;
+                            gamelog.entry(format!(
+                                "You use the {}, buying {} turns on the trace.",
+                                names.get(wants.item).unwrap(),
+                                turns
+                            ));
+                        }
+                        entities.delete(wants.item).expect("Delete failed");
+                    }
+                    Effect::SpoofTrace(turns) => {
+                        if let Some(trace) = trace.get_mut(entity) {
+                            trace.spoofed += turns;
+                            gamelog.entry(format!(
+                                "You use the {}, the trace follows a decoy for {} turns.",
+                                names.get(wants.item).unwrap(),
+                                turns
+                            ));
+                        }
+                        entities.delete(wants.item).expect("Delete failed");
+                    }
                 }
             } else {
                 let equip = equippables.get(wants.item);
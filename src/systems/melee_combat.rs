@@ -1,10 +1,78 @@
 use crate::{
-    components::{EquipBonus, Equipped, MeleeAttack, Name, Position, Stats, TakeDamage},
+    components::{
+        DamageType, EquipBonus, Equipped, InflictStatus, Inflicts, KilledBy, MeleeAttack, Name,
+        NaturalAttack, Position, Resistances, Stats, TakeDamage,
+    },
     gui::GameLog,
 };
-use specs::{prelude::*, rayon::iter::Either};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
 
-use super::particle;
+use super::{noise, particle};
+
+/// Sides of the to-hit die, a natural 1 always misses and the top face always crits
+const HIT_DIE: i32 = 20;
+/// To-hit roll plus power has to reach this plus the target's defense
+const HIT_TARGET: i32 = 10;
+/// Chance in percent for each point of defense to soak a point of damage
+const ARMOR_SOAK_CHANCE: i32 = 50;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AttackOutcome {
+    Miss,
+    Hit(i32),
+    Critical(i32),
+}
+
+impl AttackOutcome {
+    pub fn damage(&self) -> i32 {
+        match self {
+            AttackOutcome::Miss => 0,
+            AttackOutcome::Hit(damage) | AttackOutcome::Critical(damage) => *damage,
+        }
+    }
+}
+
+/// Rolls to hit, then for damage, then lets every point of armor try to soak some of it
+pub fn roll_attack(rng: &mut RandomNumberGenerator, power: i32, defense: i32) -> AttackOutcome {
+    let roll = rng.roll_dice(1, HIT_DIE);
+    let critical = roll == HIT_DIE;
+    if roll == 1 || (!critical && roll + power < HIT_TARGET + defense) {
+        return AttackOutcome::Miss;
+    }
+
+    let power = i32::max(power, 1);
+    let mut damage = power / 2 + rng.roll_dice(1, power - power / 2);
+    if critical {
+        damage *= 2;
+    }
+    let soaked = (0..defense)
+        .filter(|_| rng.roll_dice(1, 100) <= ARMOR_SOAK_CHANCE)
+        .count() as i32;
+    let damage = i32::max(0, damage - soaked);
+
+    if critical {
+        AttackOutcome::Critical(damage)
+    } else {
+        AttackOutcome::Hit(damage)
+    }
+}
+
+/// Base defense plus whatever the equipped items add
+pub fn effective_defense(
+    target: Entity,
+    stats: &Stats,
+    equipped: &ReadStorage<Equipped>,
+    bonus: &ReadStorage<EquipBonus>,
+) -> i32 {
+    (equipped, bonus)
+        .join()
+        .filter(|(e, _)| e.owner == target)
+        .fold(stats.base_defense, |defense, (_, b)| match b {
+            EquipBonus::Defense(bonus) => defense + bonus,
+            _ => defense,
+        })
+}
 
 pub struct MeleeCombatSystem;
 
@@ -20,7 +88,12 @@ impl<'a> System<'a> for MeleeCombatSystem {
         ReadStorage<'a, EquipBonus>,
         ReadStorage<'a, Equipped>,
         WriteExpect<'a, particle::RequestQueue>,
+        WriteExpect<'a, noise::NoiseQueue>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Inflicts>,
+        WriteStorage<'a, InflictStatus>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, NaturalAttack>,
     );
 
     fn run(
@@ -35,7 +108,12 @@ impl<'a> System<'a> for MeleeCombatSystem {
             bonus,
             equipped,
             mut particle_request,
+            mut noises,
             positions,
+            inflicts,
+            mut inflict_status,
+            mut rng,
+            natural,
         ): Self::SystemData,
     ) {
         for (attacker, melee, name, stat) in (&entities, &melee, &names, &stats).join() {
@@ -43,39 +121,37 @@ impl<'a> System<'a> for MeleeCombatSystem {
                 let target_stats = stats.get(melee.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(melee.target).unwrap();
-                    let (target_equipped, attacker_equipped): (Vec<_>, Vec<_>) =
-                        (&equipped, &bonus)
-                            .par_join()
-                            .filter(|(e, _)| e.owner == melee.target || e.owner == attacker)
-                            .partition_map(|(e, b)| {
-                                if e.owner == melee.target {
-                                    Either::Left(b)
-                                } else {
-                                    Either::Right(b)
-                                }
-                            });
-                    let effective_power =
-                        attacker_equipped
-                            .into_iter()
-                            .fold(stat.base_power, |power, b| match b {
-                                EquipBonus::Defense(_) => power,
-                                EquipBonus::Attack(b) => power + b,
-                            });
-                    let effective_defense = target_equipped.into_iter().fold(
-                        target_stats.base_defense,
-                        |defense, b| match b {
-                            EquipBonus::Defense(bonus) => defense + bonus,
-                            EquipBonus::Attack(_) => defense,
+                    let attacker_equipped = (&equipped, &bonus)
+                        .join()
+                        .filter(|(e, _)| e.owner == attacker)
+                        .map(|(_, b)| b);
+                    // Weapons decide the damage type, bare hands fall back to the natural one
+                    let natural_kind = natural
+                        .get(attacker)
+                        .map_or(DamageType::Kinetic, |n| n.kind);
+                    let (effective_power, kind) = attacker_equipped.into_iter().fold(
+                        (stat.base_power, natural_kind),
+                        |(power, kind), b| match b {
+                            EquipBonus::Attack { amount, kind } => (power + amount, *kind),
+                            _ => (power, kind),
                         },
                     );
-                    let dmg_amount = i32::max(0, effective_power - effective_defense);
+                    let effective_defense =
+                        effective_defense(melee.target, target_stats, &equipped, &bonus);
+                    let outcome = roll_attack(&mut rng, effective_power, effective_defense);
+                    if let Some(pos) = positions.get(attacker) {
+                        noises.make(pos.x, pos.y, noise::MELEE_VOLUME);
+                    }
 
-                    if dmg_amount == 0 {
+                    if outcome == AttackOutcome::Miss {
+                        log.entry(format!("{} misses {}", &name.name, &target_name.name));
+                    } else if outcome.damage() == 0 {
                         log.entry(format!(
                             "{} is unable to hurt {}",
                             &name.name, &target_name.name
                         ));
                     } else {
+                        let dmg_amount = outcome.damage();
                         let pos = positions.get(melee.target);
                         if let Some(pos) = pos {
                             particle_request.request(
@@ -87,11 +163,30 @@ impl<'a> System<'a> for MeleeCombatSystem {
                                 200.0,
                             );
                         };
+                        let verb = match outcome {
+                            AttackOutcome::Critical(_) => "critically hits",
+                            _ => "hits",
+                        };
                         log.entry(format!(
-                            "{} hits {}, for {} hp.",
-                            &name.name, &target_name.name, dmg_amount
+                            "{} {} {}, for {} {} damage.",
+                            &name.name, verb, &target_name.name, dmg_amount, kind
                         ));
-                        TakeDamage::new_damage(&mut damage, melee.target, dmg_amount);
+                        TakeDamage::new_damage(
+                            &mut damage,
+                            melee.target,
+                            dmg_amount,
+                            kind,
+                            Some(attacker),
+                        );
+                        if let Some(inflicts) = inflicts.get(attacker) {
+                            if rng.roll_dice(1, 100) <= inflicts.chance {
+                                InflictStatus::new_status(
+                                    &mut inflict_status,
+                                    melee.target,
+                                    inflicts.status,
+                                );
+                            }
+                        }
                     }
                 }
             }
@@ -100,19 +195,148 @@ impl<'a> System<'a> for MeleeCombatSystem {
     }
 }
 
+/// Most of a hit a resistance can ever stop
+const MAX_RESIST: i32 = 90;
+
 pub struct DamageSystem;
 
 impl<'a> System<'a> for DamageSystem {
-    type SystemData = (WriteStorage<'a, Stats>, WriteStorage<'a, TakeDamage>);
-
-    fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Stats>,
+        WriteStorage<'a, TakeDamage>,
+        ReadStorage<'a, Resistances>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, EquipBonus>,
+        WriteStorage<'a, KilledBy>,
+    );
 
-        (&mut stats, &damage)
-            .par_join()
-            .for_each(|(mut stats, damage)| {
-                stats.hp -= damage.amount;
-            });
+    fn run(
+        &mut self,
+        (entities, mut stats, mut damage, resistances, equipped, bonus, mut killed_by): Self::SystemData,
+    ) {
+        for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
+            for hit in damage.hits.iter() {
+                let equipment_resist = (&equipped, &bonus)
+                    .join()
+                    .filter(|(e, _)| e.owner == entity)
+                    .map(|(_, b)| match b {
+                        EquipBonus::Resist { kind, percent } if *kind == hit.kind => *percent,
+                        _ => 0,
+                    })
+                    .sum::<i32>();
+                let resist = resistances
+                    .get(entity)
+                    .and_then(|r| r.percent.get(&hit.kind))
+                    .copied()
+                    .unwrap_or(0)
+                    + equipment_resist;
+                let alive = stats.hp > 0;
+                stats.hp -= resisted(hit.amount, resist);
+                if let Some(killer) = hit.source.filter(|_| alive && stats.hp <= 0) {
+                    killed_by
+                        .insert(entity, KilledBy { killer })
+                        .expect("Unable to insert killer");
+                }
+            }
+        }
         damage.clear();
     }
 }
+
+/// Damage left after a resistance, weaknesses make it grow instead
+pub fn resisted(amount: i32, resist: i32) -> i32 {
+    let resist = i32::min(resist, MAX_RESIST);
+    i32::max(0, amount * (100 - resist) / 100)
+}
+
+#[test]
+fn resistances_scale_damage() {
+    assert_eq!(resisted(10, 0), 10);
+    assert_eq!(resisted(10, 50), 5);
+    assert_eq!(resisted(10, -50), 15);
+    assert_eq!(resisted(10, 200), 1);
+}
+
+#[test]
+fn attacks_are_deterministic_under_a_seed() {
+    let outcomes = |seed| {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        (0..8)
+            .map(|_| roll_attack(&mut rng, 8, 3))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(outcomes(42), outcomes(42));
+    use AttackOutcome::*;
+    assert_eq!(
+        outcomes(42),
+        vec![
+            Hit(7),
+            Miss,
+            Hit(5),
+            Hit(4),
+            Critical(10),
+            Hit(3),
+            Hit(8),
+            Hit(5)
+        ]
+    );
+}
+
+#[test]
+fn armor_softens_but_never_nullifies() {
+    let average = |power, defense| {
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let total = (0..10_000)
+            .map(|_| roll_attack(&mut rng, power, defense).damage())
+            .sum::<i32>();
+        total as f32 / 10_000.
+    };
+    let (bare, plated, fortress) = (average(8, 0), average(8, 3), average(8, 12));
+    assert!(bare > plated);
+    assert!(plated > fortress);
+    // A natural 20 always gets through, so even heavy armor can be hurt
+    assert!(fortress > 0.);
+
+    let mut rng = RandomNumberGenerator::seeded(7);
+    let misses = (0..10_000)
+        .filter(|_| roll_attack(&mut rng, 3, 1) == AttackOutcome::Miss)
+        .count();
+    // Needs an 8 or better on a d20, 35% miss
+    assert!((3_300..3_700).contains(&misses));
+}
+
+#[test]
+fn kills_are_credited_to_the_killing_blow() {
+    let mut ecs = World::new();
+    ecs.register::<Stats>();
+    ecs.register::<TakeDamage>();
+    ecs.register::<Resistances>();
+    ecs.register::<Equipped>();
+    ecs.register::<EquipBonus>();
+    ecs.register::<KilledBy>();
+
+    let stats = Stats {
+        base_power: 1,
+        base_hp: 5,
+        hp: 5,
+        base_defense: 0,
+        compute: 0,
+        base_compute: 0,
+    };
+    let killer = ecs.create_entity().build();
+    let survivor = ecs.create_entity().with(stats.clone()).build();
+    let victim = ecs.create_entity().with(stats).build();
+    {
+        let mut damage = ecs.write_storage::<TakeDamage>();
+        TakeDamage::new_damage(&mut damage, victim, 3, DamageType::Kinetic, None);
+        TakeDamage::new_damage(&mut damage, victim, 3, DamageType::Kinetic, Some(killer));
+        TakeDamage::new_damage(&mut damage, survivor, 3, DamageType::Kinetic, Some(killer));
+    }
+    DamageSystem.run_now(&ecs);
+
+    let killed_by = ecs.read_storage::<KilledBy>();
+    assert_eq!(killed_by.get(victim).map(|k| k.killer), Some(killer));
+    assert!(!killed_by.contains(survivor));
+}
@@ -0,0 +1,228 @@
+use crate::{
+    components::{Enemy, Item, MyTurn, Position},
+    map::Map,
+    player::Player,
+    state::RunState,
+};
+use rltk::{BaseMap, DijkstraMap, Point};
+use specs::prelude::*;
+use std::collections::VecDeque;
+
+/// How far the flow maps reach
+const MAX_DEPTH: f32 = 200.0;
+/// Scaling applied to the way to the player to get a way out, past -1 mobs prefer open space to dead ends
+const FLEE_WEIGHT: f32 = -1.2;
+
+/// Flow maps shared by every mob, rebuilt once per turn instead of pathing for each mob
+#[derive(Default)]
+pub struct Navigation {
+    pub to_player: Vec<f32>,
+    pub from_player: Vec<f32>,
+    pub to_items: Vec<f32>,
+    /// Layer, player position and item tiles the maps were last built for
+    built_for: Option<(i32, Point, Vec<usize>)>,
+}
+
+impl Navigation {
+    /// Rebuilds the maps only if the player or the items moved since the last build
+    pub fn refresh(&mut self, map: &Map, player: Point, mut items: Vec<usize>) {
+        items.sort_unstable();
+        let key = Some((map.layer, player, items));
+        if self.built_for != key {
+            let (_, _, items) = key.as_ref().expect("Key was just built");
+            self.rebuild(map, player, items);
+            self.built_for = key;
+        }
+    }
+
+    pub fn rebuild(&mut self, map: &Map, player: Point, items: &[usize]) {
+        let terrain = Terrain(map);
+        self.to_player = DijkstraMap::new(
+            map.dim_x,
+            map.dim_y,
+            &[map.coords_to_idx(player.x, player.y)],
+            &terrain,
+            MAX_DEPTH,
+        )
+        .map;
+        self.from_player = flee_map(&self.to_player, &terrain);
+        self.to_items = DijkstraMap::new(map.dim_x, map.dim_y, items, &terrain, MAX_DEPTH).map;
+    }
+
+    /// The free neighbouring tile lowest on a flow map, if the map reaches it at all
+    pub fn downhill(flow: &[f32], map: &Map, from: Point) -> Option<Point> {
+        map.get_available_exits(map.coords_to_idx(from.x, from.y))
+            .into_iter()
+            .filter_map(|(idx, _)| flow.get(idx).map(|&value| (idx, value)))
+            .filter(|&(_, value)| value < f32::MAX)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| {
+                let (x, y) = map.idx_to_coords(idx);
+                Point::new(x, y)
+            })
+    }
+
+    /// How far the nearest item is from `from`, by way of its neighbours
+    pub fn item_distance(&self, map: &Map, from: Point) -> Option<f32> {
+        Self::downhill(&self.to_items, map, from)
+            .map(|step| self.to_items[map.coords_to_idx(step.x, step.y)])
+    }
+}
+
+/// Only the terrain gets in the way, so moving mobs don't leave the flow maps stale
+struct Terrain<'a>(&'a Map);
+
+impl BaseMap for Terrain<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.0.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.0.exits_where(idx, |i| self.0.is_walkable(i))
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.0.get_pathing_distance(idx1, idx2)
+    }
+}
+
+/// Inverts a flow map and lets it settle again, so going downhill leads away from its starts
+fn flee_map(towards: &[f32], map: &Terrain) -> Vec<f32> {
+    let mut flee = towards
+        .iter()
+        .map(|&v| if v < f32::MAX { v * FLEE_WEIGHT } else { v })
+        .collect::<Vec<_>>();
+    let mut open = (0..flee.len())
+        .filter(|&idx| flee[idx] < f32::MAX)
+        .collect::<Vec<_>>();
+    open.sort_by(|&a, &b| flee[a].total_cmp(&flee[b]));
+    let mut open = VecDeque::from(open);
+    while let Some(idx) = open.pop_front() {
+        for (exit, cost) in map.get_available_exits(idx) {
+            if flee[idx] + cost < flee[exit] {
+                flee[exit] = flee[idx] + cost;
+                open.push_back(exit);
+            }
+        }
+    }
+    flee
+}
+
+pub struct NavigationSystem;
+
+impl<'a> System<'a> for NavigationSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Navigation>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Player>,
+        ReadExpect<'a, RunState>,
+        ReadStorage<'a, Enemy>,
+        ReadStorage<'a, MyTurn>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (mut navigation, map, player, run_state, enemies, turns, items, positions): Self::SystemData,
+    ) {
+        // Only worth it when some mob is about to act
+        if *run_state != RunState::Ticking || (&enemies, &turns).join().next().is_none() {
+            return;
+        }
+        let items = (&items, &positions)
+            .join()
+            .map(|(_, pos)| map.coords_to_idx(pos.x, pos.y))
+            .collect::<Vec<_>>();
+        navigation.refresh(&map, player.position.as_point(), items);
+    }
+}
+
+/// A walled square with nothing but floor inside
+#[cfg(test)]
+fn open_map(dim: i32) -> Map {
+    let mut map = Map::new(dim, dim, 0);
+    for y in 1..dim - 1 {
+        for x in 1..dim - 1 {
+            map[(x, y)] = crate::map::Tile::Floor;
+        }
+    }
+    map.reset_runtime_state();
+    map
+}
+
+#[test]
+fn flow_maps_lead_to_and_away_from_the_player() {
+    let map = open_map(20);
+    let mut navigation = Navigation::default();
+    navigation.refresh(&map, Point::new(10, 10), vec![map.coords_to_idx(3, 3)]);
+
+    let from = Point::new(14, 10);
+    let dist = |p: Point| rltk::DistanceAlg::Pythagoras.distance2d(p, Point::new(10, 10));
+    let closer = Navigation::downhill(&navigation.to_player, &map, from).unwrap();
+    assert!(dist(closer) < dist(from));
+    let farther = Navigation::downhill(&navigation.from_player, &map, from).unwrap();
+    assert!(dist(farther) > dist(from));
+    assert!(navigation.item_distance(&map, Point::new(5, 5)).unwrap() < 3.0);
+
+    // Mobs standing around when the maps were built don't cut them off
+    let mut blocked = map.clone();
+    let idx = map.coords_to_idx(12, 10);
+    blocked.passable[idx] = false;
+    navigation.rebuild(&blocked, Point::new(10, 10), &[]);
+    assert!(navigation.to_player[idx] < f32::MAX);
+
+    // Nothing moved, so the maps are left alone
+    navigation.to_player.clear();
+    navigation.refresh(&map, Point::new(10, 10), vec![map.coords_to_idx(3, 3)]);
+    assert!(navigation.to_player.is_empty());
+    navigation.refresh(&map, Point::new(11, 10), vec![map.coords_to_idx(3, 3)]);
+    assert!(!navigation.to_player.is_empty());
+}
+
+/// Run with `cargo test --release -- --ignored --nocapture navigation`
+#[test]
+#[ignore]
+fn navigation_benchmark() {
+    use std::time::Instant;
+
+    const MOBS: i32 = 100;
+    let map = open_map(128);
+    let player = Point::new(64, 64);
+    let mobs = (0..MOBS)
+        .map(|i| Point::new(2 + i % 20 * 6, 2 + i / 20 * 6))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    for &mob in mobs.iter() {
+        for (dx, dy) in [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ] {
+            let end = map.coords_to_idx(player.x + dx, player.y + dy);
+            rltk::a_star_search(map.coords_to_idx(mob.x, mob.y), end, &map);
+        }
+    }
+    let a_star = start.elapsed();
+
+    let start = Instant::now();
+    let mut navigation = Navigation::default();
+    navigation.rebuild(&map, player, &[]);
+    for &mob in mobs.iter() {
+        Navigation::downhill(&navigation.to_player, &map, mob).unwrap();
+    }
+    let dijkstra = start.elapsed();
+
+    println!(
+        "{} mobs on a 128x128 map: A* {:?}, Dijkstra maps {:?}",
+        MOBS, a_star, dijkstra
+    );
+    assert!(dijkstra < a_star);
+}
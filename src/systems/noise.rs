@@ -0,0 +1,110 @@
+use crate::{
+    components::{AiState, Enemy, Position},
+    map::Map,
+    systems::ai::SEARCH_TURNS,
+};
+use rltk::{BaseMap, Point};
+use specs::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+/// How far, in tiles, different things can be heard
+pub const MELEE_VOLUME: i32 = 8;
+pub const SHOT_VOLUME: i32 = 12;
+pub const EXPLOSION_VOLUME: i32 = 16;
+pub const RUNNING_VOLUME: i32 = 6;
+
+struct Noise {
+    x: i32,
+    y: i32,
+    volume: i32,
+}
+
+pub struct NoiseQueue {
+    noises: Vec<Noise>,
+}
+
+impl NoiseQueue {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            noises: Default::default(),
+        }
+    }
+
+    pub fn make(&mut self, x: i32, y: i32, volume: i32) {
+        self.noises.push(Noise { x, y, volume });
+    }
+}
+
+/// Every tile a noise reaches, it spreads around corners but not through walls or closed doors
+pub fn propagate(map: &Map, origin: Point, volume: i32) -> HashSet<Point> {
+    let mut heard = HashSet::from([origin]);
+    let mut open = VecDeque::from([(origin, 0)]);
+    while let Some((p, distance)) = open.pop_front() {
+        if distance >= volume {
+            continue;
+        }
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let next = Point::new(p.x + dx, p.y + dy);
+                if next.x < 0 || next.y < 0 || next.x >= map.dim_x || next.y >= map.dim_y {
+                    continue;
+                }
+                if !map.is_opaque(map.coords_to_idx(next.x, next.y)) && heard.insert(next) {
+                    open.push_back((next, distance + 1));
+                }
+            }
+        }
+    }
+    heard
+}
+
+/// Sends mobs that heard something and aren't busy to check it out
+pub struct NoiseSystem;
+
+impl<'a> System<'a> for NoiseSystem {
+    type SystemData = (
+        WriteExpect<'a, NoiseQueue>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Enemy>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, AiState>,
+    );
+
+    fn run(&mut self, (mut queue, map, enemies, positions, mut ai_states): Self::SystemData) {
+        for noise in queue.noises.drain(..) {
+            let heard = propagate(&map, Point::new(noise.x, noise.y), noise.volume);
+            for (_, pos, state) in (&enemies, &positions, &mut ai_states).join() {
+                let busy = matches!(state, AiState::Chase { .. } | AiState::Flee);
+                if !busy && heard.contains(&pos.as_point()) {
+                    *state = AiState::Search {
+                        x: noise.x,
+                        y: noise.y,
+                        turns: SEARCH_TURNS,
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn walls_muffle_noise() {
+    use crate::map::Tile;
+
+    let mut map = Map::new(20, 10, 0);
+    for y in 1..9 {
+        for x in 1..19 {
+            if x != 10 || y == 8 {
+                map[(x, y)] = Tile::Floor;
+            }
+        }
+    }
+    let heard = propagate(&map, Point::new(8, 2), 6);
+    assert!(heard.contains(&Point::new(2, 2)));
+    assert!(!heard.contains(&Point::new(12, 2)));
+    // The only way around is the gap at the bottom, too far for this noise
+    assert!(!heard.contains(&Point::new(11, 7)));
+    let heard = propagate(&map, Point::new(8, 2), 10);
+    assert!(heard.contains(&Point::new(11, 7)));
+}
@@ -100,7 +100,7 @@ pub fn particle_lifecycle(ecs: &mut World, ctx: &Rltk) {
     {
         let entities = ecs.entities();
         let mut particles = ecs.write_storage::<Particle>();
-        for (e, mut particle) in (&entities, &mut particles).join() {
+        for (e, particle) in (&entities, &mut particles).join() {
             particle.lifetime -= ctx.frame_time_ms;
             if particle.lifetime < 0. {
                 dead.push(e)
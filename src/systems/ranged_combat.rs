@@ -0,0 +1,188 @@
+use crate::{
+    components::{
+        EquipBonus, Equipped, InflictStatus, Inflicts, Name, Position, RangedAttack, RangedWeapon,
+        Stats, TakeDamage,
+    },
+    gui::GameLog,
+    map::Map,
+    systems::melee_combat::{effective_defense, roll_attack, AttackOutcome},
+};
+use rltk::{BaseMap, LineAlg, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use super::{noise, particle};
+
+/// The ranged weapon an entity fights with, either its own or an equipped one
+pub fn ranged_weapon_of(
+    owner: Entity,
+    weapons: &ReadStorage<RangedWeapon>,
+    equipped: &ReadStorage<Equipped>,
+) -> Option<RangedWeapon> {
+    weapons.get(owner).cloned().or_else(|| {
+        (equipped, weapons)
+            .join()
+            .find(|(e, _)| e.owner == owner)
+            .map(|(_, w)| w.clone())
+    })
+}
+
+/// Tiles a shot passes through on its way, without the shooter's tile
+pub fn line_of_fire(from: Point, to: Point) -> Vec<Point> {
+    rltk::line2d(LineAlg::Bresenham, from, to)
+        .into_iter()
+        .filter(|&p| p != from)
+        .collect()
+}
+
+/// Whether nothing opaque stands between the shooter and the target
+pub fn has_line_of_fire(map: &Map, from: Point, to: Point) -> bool {
+    line_of_fire(from, to)
+        .into_iter()
+        .filter(|&p| p != to)
+        .all(|p| !map.is_opaque(map.coords_to_idx(p.x, p.y)))
+}
+
+pub struct RangedCombatSystem;
+
+impl<'a> System<'a> for RangedCombatSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, RangedAttack>,
+        ReadStorage<'a, RangedWeapon>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Stats>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, EquipBonus>,
+        ReadStorage<'a, Inflicts>,
+        WriteStorage<'a, InflictStatus>,
+        WriteStorage<'a, TakeDamage>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, particle::RequestQueue>,
+        WriteExpect<'a, noise::NoiseQueue>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut ranged,
+            weapons,
+            names,
+            stats,
+            positions,
+            equipped,
+            bonus,
+            inflicts,
+            mut inflict_status,
+            mut damage,
+            map,
+            mut log,
+            mut rng,
+            mut particle_request,
+            mut noises,
+        ): Self::SystemData,
+    ) {
+        for (attacker, attack, name, stat, pos) in
+            (&entities, &ranged, &names, &stats, &positions).join()
+        {
+            if stat.hp <= 0 {
+                continue;
+            }
+            let (Some(target_stats), Some(target_pos), Some(target_name)) = (
+                stats.get(attack.target),
+                positions.get(attack.target),
+                names.get(attack.target),
+            ) else {
+                continue;
+            };
+            if target_stats.hp <= 0 {
+                continue;
+            }
+            let Some(weapon) = ranged_weapon_of(attacker, &weapons, &equipped) else {
+                continue;
+            };
+
+            let (from, to) = (pos.as_point(), target_pos.as_point());
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(from, to);
+            if distance > weapon.range as f32 || !has_line_of_fire(&map, from, to) {
+                log.entry(format!("{} has no line of fire", name));
+                continue;
+            }
+
+            noises.make(from.x, from.y, noise::SHOT_VOLUME);
+            for (i, p) in line_of_fire(from, to).into_iter().enumerate() {
+                particle_request.request(
+                    p.x,
+                    p.y,
+                    rltk::RGB::named(rltk::YELLOW),
+                    rltk::RGB::named(rltk::BLACK),
+                    rltk::to_cp437('*'),
+                    50.0 + 25.0 * i as f32,
+                );
+            }
+
+            let defense = effective_defense(attack.target, target_stats, &equipped, &bonus);
+            match roll_attack(&mut rng, weapon.damage, defense) {
+                AttackOutcome::Miss => {
+                    log.entry(format!("{} shoots at {} and misses", name, target_name))
+                }
+                AttackOutcome::Hit(0) | AttackOutcome::Critical(0) => log.entry(format!(
+                    "{} shoots {}, but fails to hurt it",
+                    name, target_name
+                )),
+                outcome => {
+                    let verb = match outcome {
+                        AttackOutcome::Critical(_) => "critically shoots",
+                        _ => "shoots",
+                    };
+                    log.entry(format!(
+                        "{} {} {}, for {} {} damage.",
+                        name,
+                        verb,
+                        target_name,
+                        outcome.damage(),
+                        weapon.kind
+                    ));
+                    TakeDamage::new_damage(
+                        &mut damage,
+                        attack.target,
+                        outcome.damage(),
+                        weapon.kind,
+                        Some(attacker),
+                    );
+                    if let Some(inflicts) = inflicts.get(attacker) {
+                        if rng.roll_dice(1, 100) <= inflicts.chance {
+                            InflictStatus::new_status(
+                                &mut inflict_status,
+                                attack.target,
+                                inflicts.status,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        ranged.clear();
+    }
+}
+
+#[test]
+fn walls_block_line_of_fire() {
+    use crate::map::Tile;
+
+    let mut map = Map::new(10, 10, 0);
+    for x in 1..9 {
+        map[(x, 5)] = Tile::Floor;
+        map[(x, 6)] = Tile::Floor;
+    }
+    assert!(has_line_of_fire(&map, Point::new(1, 5), Point::new(8, 5)));
+    map[(4, 5)] = Tile::Door;
+    assert!(!has_line_of_fire(&map, Point::new(1, 5), Point::new(8, 5)));
+    assert!(has_line_of_fire(&map, Point::new(1, 6), Point::new(8, 6)));
+    // Shooting at something standing in a doorway is fine
+    assert!(has_line_of_fire(&map, Point::new(1, 5), Point::new(4, 5)));
+}
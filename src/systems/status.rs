@@ -0,0 +1,265 @@
+use crate::{
+    components::{
+        Blinded, Burning, Confused, DamageType, Emp, InflictStatus, MyTurn, Name, Position,
+        StatusEffect, Stunned, TakeDamage, Viewshed,
+    },
+    gui::GameLog,
+    map::Map,
+    player::Player,
+    state::RunState,
+};
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+/// Applies freshly inflicted status effects and counts them down
+/// whenever their victim gets a turn
+pub struct StatusSystem;
+
+impl<'a> System<'a> for StatusSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, InflictStatus>,
+        WriteStorage<'a, MyTurn>,
+        WriteStorage<'a, Stunned>,
+        WriteStorage<'a, Burning>,
+        WriteStorage<'a, Emp>,
+        WriteStorage<'a, Confused>,
+        WriteStorage<'a, Blinded>,
+        WriteStorage<'a, Viewshed>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, TakeDamage>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadExpect<'a, Player>,
+        ReadExpect<'a, RunState>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut inflict,
+            mut turns,
+            mut stunned,
+            mut burning,
+            mut emp,
+            mut confused,
+            mut blinded,
+            mut viewsheds,
+            mut positions,
+            mut damage,
+            names,
+            map,
+            mut log,
+            mut rng,
+            player,
+            run_state,
+        ): Self::SystemData,
+    ) {
+        for (victim, pending) in (&entities, &inflict).join() {
+            let name = names.get(victim).map_or("Something", |n| &n.name);
+            for effect in pending.effects.iter() {
+                match *effect {
+                    StatusEffect::Stun(t) => {
+                        refresh(&mut stunned, victim, Stunned { turns: t }, |s| &mut s.turns);
+                        log.entry(format!("{} is stunned", name));
+                    }
+                    StatusEffect::Burn { turns: t, damage } => {
+                        refresh(&mut burning, victim, Burning { turns: t, damage }, |s| {
+                            &mut s.turns
+                        });
+                        log.entry(format!("{} catches fire", name));
+                    }
+                    StatusEffect::Emp(t) => {
+                        refresh(&mut emp, victim, Emp { turns: t }, |s| &mut s.turns);
+                        log.entry(format!("{} is hit by an EMP", name));
+                    }
+                    StatusEffect::Confuse(t) => {
+                        refresh(&mut confused, victim, Confused { turns: t }, |s| {
+                            &mut s.turns
+                        });
+                        log.entry(format!("{} is confused", name));
+                    }
+                    StatusEffect::Blind(t) => {
+                        refresh(&mut blinded, victim, Blinded { turns: t }, |s| &mut s.turns);
+                        if let Some(viewshed) = viewsheds.get_mut(victim) {
+                            viewshed.dirty = true;
+                        }
+                        log.entry(format!("{} loses its sensor feed", name));
+                    }
+                }
+            }
+        }
+        inflict.clear();
+
+        if *run_state != RunState::Ticking {
+            return;
+        }
+        let acting = (&entities, &turns)
+            .join()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        for e in acting {
+            let name = names.get(e).map_or("Something", |n| &n.name);
+            let is_player = e == player.entity;
+            let mut expired = Vec::new();
+
+            if let Some(burn) = burning.get(e) {
+                TakeDamage::new_damage(&mut damage, e, burn.damage, DamageType::Thermal, None);
+                log.entry(format!("{} burns for {} hp", name, burn.damage));
+            }
+            if tick(&mut burning, e, |s| &mut s.turns) == Some(true) {
+                expired.push("burning");
+            }
+            if tick(&mut emp, e, |s| &mut s.turns) == Some(true) {
+                expired.push("jammed");
+            }
+            if tick(&mut blinded, e, |s| &mut s.turns) == Some(true) {
+                if let Some(viewshed) = viewsheds.get_mut(e) {
+                    viewshed.dirty = true;
+                }
+                expired.push("blind");
+            }
+
+            let was_confused = tick(&mut confused, e, |s| &mut s.turns);
+            if was_confused == Some(true) {
+                expired.push("confused");
+            }
+            let was_stunned = tick(&mut stunned, e, |s| &mut s.turns);
+            if was_stunned == Some(true) {
+                expired.push("stunned");
+            }
+            if is_player {
+                for status in expired {
+                    log.entry(format!("You are no longer {}", status));
+                }
+            }
+
+            if was_stunned.is_some() {
+                turns.remove(e);
+                continue;
+            }
+
+            // The player stumbles around on their own turn, see `step_player`
+            if was_confused.is_some() && !is_player {
+                if let Some(pos) = positions.get_mut(e) {
+                    let (x, y) = (pos.x + rng.range(-1, 2), pos.y + rng.range(-1, 2));
+                    if x > 0
+                        && y > 0
+                        && x < map.dim_x
+                        && y < map.dim_y
+                        && map.passable[map.coords_to_idx(x, y)]
+                    {
+                        pos.x = x;
+                        pos.y = y;
+                        if let Some(viewshed) = viewsheds.get_mut(e) {
+                            viewshed.dirty = true;
+                        }
+                    }
+                }
+                turns.remove(e);
+            }
+        }
+    }
+}
+
+/// Inserts a status or extends the one already there
+fn refresh<T: Component>(
+    store: &mut WriteStorage<T>,
+    entity: Entity,
+    status: T,
+    turns: fn(&mut T) -> &mut i32,
+) {
+    let mut status = status;
+    let new_turns = *turns(&mut status);
+    if let Some(existing) = store.get_mut(entity) {
+        let existing_turns = turns(existing);
+        *existing_turns = i32::max(*existing_turns, new_turns);
+    } else {
+        store
+            .insert(entity, status)
+            .expect("Unable to insert status");
+    }
+}
+
+/// Counts a status down, returns `None` if the entity doesn't have it
+/// and whether it just ran out otherwise
+fn tick<T: Component>(
+    store: &mut WriteStorage<T>,
+    entity: Entity,
+    turns: fn(&mut T) -> &mut i32,
+) -> Option<bool> {
+    let status = store.get_mut(entity)?;
+    let turns = turns(status);
+    *turns -= 1;
+    let expired = *turns <= 0;
+    if expired {
+        store.remove(entity);
+    }
+    Some(expired)
+}
+
+#[test]
+fn statuses_run_out_on_victims_turns() {
+    let mut ecs = World::new();
+    ecs.register::<InflictStatus>();
+    ecs.register::<MyTurn>();
+    ecs.register::<Stunned>();
+    ecs.register::<Burning>();
+    ecs.register::<Emp>();
+    ecs.register::<Confused>();
+    ecs.register::<Blinded>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Position>();
+    ecs.register::<TakeDamage>();
+    ecs.register::<Name>();
+    ecs.insert(Map::new(8, 8, 0));
+    ecs.insert(GameLog::default());
+    ecs.insert(RandomNumberGenerator::seeded(1));
+    ecs.insert(RunState::Ticking);
+
+    let player = ecs.create_entity().build();
+    ecs.insert(Player {
+        entity: player,
+        position: Position { x: 0, y: 0 },
+    });
+    let victim = ecs.create_entity().build();
+    {
+        let mut inflict = ecs.write_storage::<InflictStatus>();
+        InflictStatus::new_status(&mut inflict, victim, StatusEffect::Stun(2));
+        InflictStatus::new_status(
+            &mut inflict,
+            victim,
+            StatusEffect::Burn {
+                turns: 1,
+                damage: 3,
+            },
+        );
+    }
+
+    let take_turn = |ecs: &mut World| {
+        ecs.write_storage::<MyTurn>()
+            .insert(victim, MyTurn)
+            .unwrap();
+        StatusSystem.run_now(ecs);
+        ecs.read_storage::<MyTurn>().contains(victim)
+    };
+    assert!(!take_turn(&mut ecs));
+    assert_eq!(
+        ecs.read_storage::<TakeDamage>()
+            .get(victim)
+            .map(|d| d.hits.clone()),
+        Some(vec![crate::components::Damage {
+            amount: 3,
+            kind: DamageType::Thermal,
+            source: None,
+        }])
+    );
+    assert!(!ecs.read_storage::<Burning>().contains(victim));
+    assert!(!take_turn(&mut ecs));
+    assert!(take_turn(&mut ecs));
+    assert!(!ecs.read_storage::<Stunned>().contains(victim));
+}
@@ -1,39 +1,98 @@
 use specs::prelude::*;
 
-use crate::{
-    components::{TakeDamage, TraceTimer},
-    gui::GameLog,
-    player::Player,
-    state::RunState,
-};
+use crate::{components::TraceTimer, gui::GameLog, map::Map, player::Player, state::RunState};
+
+/// Turns past the deadline between each rise of the alert level
+pub const ALERT_STEP: i32 = 50;
+pub const MAX_ALERT: i32 = 5;
+/// Turns bought by slipping down to a new layer
+pub const LAYER_BONUS: i32 = 200;
+/// Trace lost by pulling a layer map off a service terminal
+pub const DOWNLOAD_COST: i32 = 20;
+/// Compute spent and trace bought by scrubbing a service terminal's logs
+pub const SCRUB_COMPUTE: i32 = 5;
+pub const SCRUB_BONUS: i32 = 80;
+/// Layers between each step up in how fast the trace closes in
+const DECAY_LAYERS: i32 = 4;
+
+/// How hard the corporation is hunting the player, zero while the trace hasn't completed
+pub fn alert_level(timer: i32) -> i32 {
+    if timer >= 0 {
+        0
+    } else {
+        (1 + (-timer - 1) / ALERT_STEP).min(MAX_ALERT)
+    }
+}
+
+/// Turns between reinforcements at an alert level, shrinking as the alert rises
+fn reinforcement_interval(alert: i32) -> i32 {
+    (30 - 5 * alert).max(5)
+}
+
+/// Trace turns lost every turn on a layer, deeper layers are watched more closely
+pub fn trace_decay(layer: i32) -> i32 {
+    1 + layer.max(0) / DECAY_LAYERS
+}
 
 pub struct TraceTimerSystem;
 
 impl<'a> System<'a> for TraceTimerSystem {
-    #[allow(clippy::type_complexity)]
     type SystemData = (
         WriteStorage<'a, TraceTimer>,
         ReadExpect<'a, RunState>,
         ReadExpect<'a, Player>,
-        WriteStorage<'a, TakeDamage>,
+        ReadExpect<'a, Map>,
         WriteExpect<'a, GameLog>,
     );
 
-    fn run(&mut self, (mut trace, state, player, mut damage, mut log): Self::SystemData) {
-        if *state == RunState::PlayerTurn {
-            let player_trace = trace
-                .get_mut(player.entity)
-                .expect("Player has no trace component");
-            player_trace.timer -= 1;
-            if player_trace.timer <= 10 {
-                log.entry("Corporate hackers are almost caught up to you".into());
-                if player_trace.timer < 0 {
-                    log.entry("Netrunners have tracked you down".into());
-                    damage
-                        .insert(player.entity, TakeDamage { amount: 1 })
-                        .expect("Could not damage player");
-                }
+    fn run(&mut self, (mut trace, state, player, map, mut log): Self::SystemData) {
+        if *state != RunState::PlayerTurn {
+            return;
+        }
+        let trace = trace
+            .get_mut(player.entity)
+            .expect("Player has no trace component");
+        let before = trace.timer;
+        if trace.spoofed > 0 {
+            trace.spoofed -= 1;
+            if trace.spoofed == 0 {
+                log.entry("The corporation sees through your decoy".into());
             }
+        } else {
+            trace.timer -= trace_decay(map.layer);
+        }
+        if before > 10 && trace.timer <= 10 {
+            log.entry("Corporate hackers are almost caught up to you".into());
         }
+        let alert = alert_level(trace.timer);
+        if alert > trace.alert {
+            if trace.alert == 0 {
+                log.entry("Netrunners have tracked you down".into());
+            }
+            log.entry(format!(
+                "Corporate alert level {}, hunters dispatched",
+                alert
+            ));
+            trace.pending_hunters += alert;
+        } else if alert > 0 {
+            let interval = reinforcement_interval(alert);
+            if -before.min(0) / interval < -trace.timer / interval {
+                trace.pending_hunters += 1;
+            }
+        }
+        trace.alert = alert;
     }
 }
+
+#[test]
+fn alert_rises_past_the_deadline() {
+    assert_eq!(alert_level(400), 0);
+    assert_eq!(alert_level(0), 0);
+    assert_eq!(alert_level(-1), 1);
+    assert_eq!(alert_level(-ALERT_STEP), 1);
+    assert_eq!(alert_level(-ALERT_STEP - 1), 2);
+    assert_eq!(alert_level(-10_000), MAX_ALERT);
+    assert!(reinforcement_interval(MAX_ALERT) < reinforcement_interval(1));
+    assert_eq!(trace_decay(0), 1);
+    assert!(trace_decay(2 * DECAY_LAYERS) > trace_decay(DECAY_LAYERS));
+}
@@ -1,5 +1,5 @@
 use crate::{
-    components::{Control, Position, Viewshed},
+    components::{Blinded, Control, Position, Viewshed},
     map::Map,
 };
 use rltk::{field_of_view_set, Point};
@@ -14,15 +14,20 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteStorage<'a, Viewshed>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Control>,
+        ReadStorage<'a, Blinded>,
     );
 
-    fn run(&mut self, (mut map, entities, mut viewshed, pos, player): Self::SystemData) {
+    fn run(&mut self, (mut map, entities, mut viewshed, pos, player, blinded): Self::SystemData) {
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
                 viewshed.visible_tiles.clear();
-                viewshed.visible_tiles =
-                    field_of_view_set(Point::new(pos.x, pos.y), viewshed.range, &*map);
+                let range = if blinded.contains(ent) {
+                    0
+                } else {
+                    viewshed.range
+                };
+                viewshed.visible_tiles = field_of_view_set(Point::new(pos.x, pos.y), range, &*map);
                 viewshed
                     .visible_tiles
                     .retain(|p| p.x >= 0 && p.x < map.dim_x && p.y >= 0 && p.y < map.dim_y);